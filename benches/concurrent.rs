@@ -0,0 +1,80 @@
+//! Throughput of hit-heavy [PinnedMap::get_or_insert] workloads under rayon,
+//! and of bulk loading with [PinnedMap::get_or_insert_many].
+//!
+//! Run with `cargo bench --bench concurrent`.
+
+use pinned_bucket::PinnedMap;
+use rayon::prelude::*;
+use std::{
+    collections::BTreeMap,
+    hint::black_box,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+const KEYS: usize = 1 << 10;
+const OPS: usize = 1 << 22;
+/// Items per call of [PinnedMap::get_or_insert_many].
+const BATCH: usize = 64;
+
+fn measure(name: &str, f: impl Fn(usize) + Sync) {
+    // Warm up.
+    (0..OPS / 16).into_par_iter().for_each(&f);
+    let start = Instant::now();
+    (0..OPS).into_par_iter().for_each(&f);
+    let elapsed = start.elapsed();
+    report(name, elapsed);
+}
+
+/// The same as [measure], but `f` is given the first of [BATCH] items.
+fn measure_batches(name: &str, f: impl Fn(usize) + Sync) {
+    let batches = |n: usize| (0..n / BATCH).into_par_iter().map(|b| b * BATCH);
+    batches(OPS / 16).for_each(&f);
+    let start = Instant::now();
+    batches(OPS).for_each(&f);
+    let elapsed = start.elapsed();
+    report(name, elapsed);
+}
+
+fn report(name: &str, elapsed: Duration) {
+    let rate = OPS as f64 / elapsed.as_secs_f64() / 1e6;
+    println!("{name:<32} {elapsed:>12.2?} {rate:>10.2} Mops/s");
+}
+
+fn main() {
+    println!(
+        "{} threads, {KEYS} keys, {OPS} ops",
+        rayon::current_num_threads()
+    );
+
+    // What `get_or_insert` used to do: always take the write lock.
+    let baseline = RwLock::new(BTreeMap::new());
+    measure("write lock (baseline)", |i| {
+        let mut guard = baseline.write().unwrap();
+        black_box(*guard.entry(i % KEYS).or_insert(i));
+    });
+
+    let map = PinnedMap::new();
+    measure("PinnedMap::get_or_insert", |i| {
+        black_box(map.get_or_insert(i % KEYS, i));
+    });
+
+    let map = PinnedMap::new();
+    measure("PinnedMap::get_or_insert_with", |i| {
+        black_box(map.get_or_insert_with(i % KEYS, || i));
+    });
+
+    // Bulk loading, with keys mostly missing.
+    let map = PinnedMap::new();
+    measure_batches("get_or_insert loop", |first| {
+        for i in first..first + BATCH {
+            black_box(map.get_or_insert(i, i));
+        }
+    });
+
+    let map = PinnedMap::new();
+    measure_batches("get_or_insert_many", |first| {
+        let refs: Vec<_> = map.get_or_insert_many((first..first + BATCH).map(|i| (i, i)));
+        black_box(refs);
+    });
+}
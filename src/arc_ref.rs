@@ -0,0 +1,226 @@
+use alloc::sync::{Arc, Weak};
+use core::{
+    fmt::{self, Debug, Display},
+    ops::Deref,
+    ptr::NonNull,
+};
+
+/// An owning reference to an item in a container behind an [Arc],
+/// which keeps the whole container alive.
+///
+/// Unlike the references returned by [PinnedList::push](crate::PinnedList::push),
+/// it is not tied to a borrow of the container,
+/// so it can be sent to spawned threads or stored in long-lived structs.
+///
+/// ```rust
+/// use pinned_bucket::*;
+/// use std::{sync::Arc, thread};
+/// let v = Arc::new(PinnedList::new());
+/// let a = v.push_arc((1, "a"));
+/// drop(v);
+/// let b = ArcRef::map(a.clone(), |(_, s)| s);
+/// thread::spawn(move || assert_eq!(*b, "a")).join().unwrap();
+/// assert_eq!(a.0, 1);
+/// ```
+pub struct ArcRef<T: ?Sized> {
+    /// Shall not be read. Only kept here to keep the item alive.
+    #[allow(unused)]
+    owner: Arc<dyn Send + Sync>,
+    ptr: NonNull<T>,
+}
+
+// Only shared references are handed out.
+unsafe impl<T: ?Sized + Sync> Send for ArcRef<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for ArcRef<T> {}
+
+impl<T: ?Sized> ArcRef<T> {
+    /// Borrow an item from `owner` with `f`,
+    /// and keep `owner` alive as long as the item is referred to.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// use std::sync::Arc;
+    /// let v = Arc::new(PinnedMap::new());
+    /// v.insert(1, 2);
+    /// let a = ArcRef::new(&v, |v| v.get(&1).unwrap());
+    /// assert_eq!(*a, 2);
+    /// ```
+    pub fn new<C: Send + Sync + 'static>(owner: &Arc<C>, f: impl FnOnce(&C) -> &T) -> Self {
+        let ptr = NonNull::from(f(owner));
+        Self {
+            owner: owner.clone(),
+            ptr,
+        }
+    }
+    /// Project into a part of the item.
+    ///
+    /// It is an associated function, so that it won't shadow methods of `T`.
+    pub fn map<U: ?Sized>(this: Self, f: impl FnOnce(&T) -> &U) -> ArcRef<U> {
+        let ptr = NonNull::from(f(&this));
+        ArcRef {
+            owner: this.owner,
+            ptr,
+        }
+    }
+    /// Project into a part of the item, if any,
+    /// or hand the [ArcRef] back.
+    pub fn filter_map<U: ?Sized>(
+        this: Self,
+        f: impl FnOnce(&T) -> Option<&U>,
+    ) -> Result<ArcRef<U>, Self> {
+        match f(&this).map(NonNull::from) {
+            Some(ptr) => Ok(ArcRef {
+                owner: this.owner,
+                ptr,
+            }),
+            None => Err(this),
+        }
+    }
+    /// Check if two [ArcRef]s refer to the same item.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        core::ptr::addr_eq(this.ptr.as_ptr(), other.ptr.as_ptr())
+    }
+    /// Create a [WeakRef] to the same item,
+    /// which doesn't keep the container alive.
+    pub fn downgrade(this: &Self) -> WeakRef<T> {
+        WeakRef {
+            owner: Arc::downgrade(&this.owner),
+            ptr: this.ptr,
+        }
+    }
+}
+
+/// A weak reference to an item in a container behind an [Arc],
+/// which doesn't keep the container alive.
+///
+/// Constructed by [ArcRef::downgrade].
+///
+/// ```rust
+/// use pinned_bucket::*;
+/// use std::sync::Arc;
+/// let v = Arc::new(PinnedList::new());
+/// let a = ArcRef::downgrade(&v.push_arc(1));
+/// assert_eq!(a.upgrade().as_deref(), Some(&1));
+/// drop(v);
+/// assert!(a.upgrade().is_none());
+/// ```
+pub struct WeakRef<T: ?Sized> {
+    owner: Weak<dyn Send + Sync>,
+    ptr: NonNull<T>,
+}
+
+// Only shared references are handed out, through [ArcRef].
+unsafe impl<T: ?Sized + Sync> Send for WeakRef<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for WeakRef<T> {}
+
+impl<T: ?Sized> WeakRef<T> {
+    /// Get an [ArcRef] to the item if the container is still alive.
+    pub fn upgrade(&self) -> Option<ArcRef<T>> {
+        Some(ArcRef {
+            owner: self.owner.upgrade()?,
+            ptr: self.ptr,
+        })
+    }
+    /// Check if two [WeakRef]s refer to the same item.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        core::ptr::addr_eq(this.ptr.as_ptr(), other.ptr.as_ptr())
+    }
+}
+
+impl<T: ?Sized> Clone for WeakRef<T> {
+    fn clone(&self) -> Self {
+        Self {
+            owner: self.owner.clone(),
+            ptr: self.ptr,
+        }
+    }
+}
+
+impl<T: ?Sized> Debug for WeakRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("(WeakRef)")
+    }
+}
+
+impl<T: ?Sized> Deref for ArcRef<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // The item lives as long as its owner.
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T: ?Sized> Clone for ArcRef<T> {
+    fn clone(&self) -> Self {
+        Self {
+            owner: self.owner.clone(),
+            ptr: self.ptr,
+        }
+    }
+}
+
+impl<T: ?Sized> AsRef<T> for ArcRef<T> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+impl<T: ?Sized + Debug> Debug for ArcRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + Display> Display for ArcRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PinnedList, PinnedMap};
+    use std::thread;
+
+    #[test]
+    fn arc_ref() {
+        let list = Arc::new(PinnedList::new());
+        let refs: Vec<_> = (0..100).map(|i| list.push_arc(i)).collect();
+        drop(list);
+        let handles: Vec<_> = refs
+            .into_iter()
+            .map(|r| thread::spawn(move || *r))
+            .collect();
+        let sum: i32 = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(sum, 99 * 100 / 2);
+
+        let map = Arc::new(PinnedMap::new());
+        let a = map.insert_arc(1, String::from("a"));
+        map.insert(1, String::from("b"));
+        // The shadowed item is still alive.
+        let a = ArcRef::map(a, String::as_str);
+        assert_eq!(&*a, "a");
+        assert_eq!(map.get_arc(&1).as_deref().map(String::as_str), Some("b"));
+        assert!(map.get_arc(&2).is_none());
+    }
+
+    #[test]
+    fn weak_ref() {
+        let map = Arc::new(PinnedMap::new());
+        let weak: Vec<_> = (0..100)
+            .map(|i| ArcRef::downgrade(&map.insert_arc(i, i * i)))
+            .collect();
+        thread::scope(|s| {
+            for (i, w) in weak.iter().enumerate() {
+                s.spawn(move || assert_eq!(w.upgrade().as_deref(), Some(&(i * i))));
+            }
+        });
+        let strong = weak[1].upgrade().unwrap();
+        drop(map);
+        // Kept alive by `strong`.
+        assert!(weak[0].upgrade().is_some());
+        drop(strong);
+        assert!(weak.iter().all(|w| w.upgrade().is_none()));
+    }
+}
@@ -0,0 +1,127 @@
+use alloc::vec::Vec;
+use core::{
+    fmt::{self, Display},
+    sync::atomic::{AtomicU64, Ordering},
+};
+use std::error::Error;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Issuer of the [Checkpoint]s of a container,
+/// with an identity assigned on first use,
+/// and the rollbacks done on it.
+///
+/// A clone gets a new identity.
+#[derive(Debug, Default)]
+pub(crate) struct Issuer {
+    id: AtomicU64,
+    /// Number of rollbacks done.
+    epoch: u64,
+    /// The furthest position each earlier epoch has been rolled back to,
+    /// as runs of epochs starting from the given ones.
+    ///
+    /// Positions grow with epochs,
+    /// as rolling back to a position discards everything after it
+    /// in all earlier epochs.
+    floors: Vec<(u64, u64)>,
+}
+
+impl Issuer {
+    pub(crate) const fn new() -> Self {
+        Self {
+            id: AtomicU64::new(0),
+            epoch: 0,
+            floors: Vec::new(),
+        }
+    }
+    fn id(&self) -> u64 {
+        let id = self.id.load(Ordering::Relaxed);
+        if id != 0 {
+            return id;
+        }
+        let new = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        match self
+            .id
+            .compare_exchange(0, new, Ordering::Relaxed, Ordering::Relaxed)
+        {
+            Ok(_) => new,
+            Err(id) => id,
+        }
+    }
+    pub(crate) fn checkpoint(&self, pos: u64) -> Checkpoint {
+        Checkpoint {
+            owner: self.id(),
+            epoch: self.epoch,
+            pos,
+        }
+    }
+    /// Get the position of a [Checkpoint] issued by this container,
+    /// and discard all checkpoints issued after it.
+    ///
+    /// Nothing is changed if it is rejected.
+    pub(crate) fn rollback(&mut self, checkpoint: Checkpoint) -> Result<u64, RollbackError> {
+        if checkpoint.owner != self.id() {
+            return Err(RollbackError::Foreign);
+        }
+        if checkpoint.epoch < self.epoch {
+            let run = self.floors.partition_point(|(e, _)| *e <= checkpoint.epoch);
+            let (_, floor) = self.floors[run - 1];
+            if checkpoint.pos > floor {
+                return Err(RollbackError::Discarded);
+            }
+        }
+        let mut start = self.epoch;
+        while let Some(&(e, floor)) = self.floors.last() {
+            if floor < checkpoint.pos {
+                break;
+            }
+            start = e;
+            self.floors.pop();
+        }
+        self.floors.push((start, checkpoint.pos));
+        self.epoch += 1;
+        Ok(checkpoint.pos)
+    }
+}
+
+impl Clone for Issuer {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+/// A point to roll a container back to.
+///
+/// Constructed by [PinnedList::checkpoint](crate::PinnedList::checkpoint)
+/// or [PinnedMap::checkpoint](crate::PinnedMap::checkpoint).
+///
+/// It is a token checked at runtime, not by the compiler:
+/// it is only accepted by the same container,
+/// until it is rolled back to an earlier point,
+/// and rejected with a [RollbackError] otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    owner: u64,
+    epoch: u64,
+    pos: u64,
+}
+
+/// Why a [Checkpoint] is rejected by a rollback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RollbackError {
+    /// It was taken on another container.
+    Foreign,
+    /// It was taken after a point the container has since been rolled back to.
+    Discarded,
+}
+
+impl Display for RollbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Foreign => f.write_str("checkpoint taken on another container"),
+            Self::Discarded => f.write_str("checkpoint discarded by an earlier rollback"),
+        }
+    }
+}
+
+impl Error for RollbackError {}
@@ -0,0 +1,388 @@
+//! An insertion-ordered map of pinned items.
+
+use super::PANIC;
+use crate::policy::{DuplicateKey, Policy, Shadow};
+use alloc::boxed::Box;
+use core::{
+    borrow::Borrow,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    mem,
+    ops::Deref,
+    pin::Pin,
+    ptr::NonNull,
+};
+use std::{collections::HashMap, fmt::Debug, sync::RwLock};
+
+pub use iter::{Iter, Keys, Values};
+
+mod iter;
+
+/// A key in [Inner::entries], referred to by [Inner::index].
+struct KeyRef<K>(NonNull<K>);
+
+// It is only a shared reference to the key.
+unsafe impl<K: Sync> Send for KeyRef<K> {}
+unsafe impl<K: Sync> Sync for KeyRef<K> {}
+
+impl<K> KeyRef<K> {
+    fn get(&self) -> &K {
+        // Keys are pinned and live as long as the index.
+        unsafe { self.0.as_ref() }
+    }
+}
+
+impl<K: Hash> Hash for KeyRef<K> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.get().hash(state)
+    }
+}
+
+impl<K: PartialEq> PartialEq for KeyRef<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.get() == other.get()
+    }
+}
+
+impl<K: Eq> Eq for KeyRef<K> {}
+
+/// A borrowed form of keys to look up [Inner::index] with.
+///
+/// `KeyRef<K>` can't implement `Borrow<Q>` for every `Q` that `K` does,
+/// which conflicts with `Borrow<T> for T`,
+/// so the borrowed form is wrapped.
+#[repr(transparent)]
+struct Query<Q: ?Sized>(Q);
+
+impl<Q: ?Sized> Query<Q> {
+    fn new(q: &Q) -> &Self {
+        // It is transparent.
+        unsafe { &*(q as *const Q as *const Self) }
+    }
+}
+
+impl<K: Borrow<Q>, Q: ?Sized> Borrow<Query<Q>> for KeyRef<K> {
+    fn borrow(&self) -> &Query<Q> {
+        Query::new(self.get().borrow())
+    }
+}
+
+impl<Q: Hash + ?Sized> Hash for Query<Q> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl<Q: PartialEq + ?Sized> PartialEq for Query<Q> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<Q: Eq + ?Sized> Eq for Query<Q> {}
+
+type Entry<K, V> = (Pin<Box<K>>, Pin<Box<V>>);
+
+struct Inner<K, V> {
+    index: HashMap<KeyRef<K>, usize>,
+    entries: Vec<Entry<K, V>>,
+    shadowed: Vec<Pin<Box<V>>>,
+}
+
+impl<K, V> Inner<K, V> {
+    /// Get the position of a key.
+    fn find<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q> + Hash + Eq,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.index.get(Query::new(key)).copied()
+    }
+}
+
+impl<K: Hash + Eq, V> Inner<K, V> {
+    /// Push an item whose key is absent.
+    fn push<'a>(&mut self, key: K, value: V) -> &'a V {
+        let key = Box::pin(key);
+        let value = Box::pin(value);
+        let r = erase(&value);
+        let k = KeyRef(NonNull::from(key.deref()));
+        self.index.insert(k, self.entries.len());
+        self.entries.push((key, value));
+        r
+    }
+}
+
+fn erase<'a, T>(t: &Pin<Box<T>>) -> &'a T {
+    let r = t.deref();
+    unsafe { mem::transmute::<&T, &T>(r) }
+}
+
+/// A map from `K` to `Pin<Box<V>>`, ordered by insertion,
+/// with both keys and values pinned.
+///
+/// Lookups by key are backed by a hash index,
+/// and items can also be looked up by their position.
+///
+/// ```rust
+/// use pinned_bucket::*;
+/// let v = PinnedIndexMap::new();
+/// v.insert("b", 1);
+/// v.insert("a", 2);
+/// assert_eq!(v.get("a"), Some(&2));
+/// assert_eq!(v.get_index(0), Some((&"b", &1)));
+/// assert_eq!(v.get_index_of("a"), Some(1));
+/// assert_eq!(v.keys().collect::<Vec<_>>(), [&"b", &"a"]);
+/// ```
+///
+/// As the items inside are still managed by the container,
+/// codes below won't compile.
+///
+/// ```compile_fail
+/// use pinned_bucket::*;
+/// let v = PinnedIndexMap::new();
+/// let a = v.insert(1, 2);
+/// drop(v);
+/// assert_eq!(a, &2);
+/// ```
+///
+/// What happens when inserting an item with a key already present
+/// is decided by the [Policy] `P`, as in [PinnedMap](crate::PinnedMap).
+/// A shadowed item keeps its position, which is taken over by the new item.
+///
+/// If you [clone](Clone::clone) this,
+/// references to items in new container will be different to
+/// references to those in old container.
+pub struct PinnedIndexMap<K, V, P = Shadow> {
+    inner: RwLock<Inner<K, V>>,
+    policy: PhantomData<fn() -> P>,
+}
+impl<K, V, P> Default for PinnedIndexMap<K, V, P> {
+    fn default() -> Self {
+        Self {
+            inner: RwLock::new(Inner {
+                index: HashMap::new(),
+                entries: Vec::new(),
+                shadowed: Vec::new(),
+            }),
+            policy: PhantomData,
+        }
+    }
+}
+impl<K, V> PinnedIndexMap<K, V> {
+    /// Create an empty [PinnedIndexMap] with the default [Shadow] policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+impl<K, V, P> PinnedIndexMap<K, V, P> {
+    /// Create an empty [PinnedIndexMap] with given [Policy].
+    pub fn with_policy(_policy: P) -> Self {
+        Self::default()
+    }
+    /// Get the number of elements in [PinnedIndexMap].
+    pub fn len(&self) -> usize {
+        self.inner.read().expect(PANIC).entries.len()
+    }
+    /// Check if the [PinnedIndexMap] is empty.
+    pub fn is_empty(&self) -> bool {
+        self.inner.read().expect(PANIC).entries.is_empty()
+    }
+    /// Check if the [PinnedIndexMap] contains a key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q> + Hash + Eq,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.read().expect(PANIC).find(key).is_some()
+    }
+    /// Push an item into the [PinnedIndexMap]
+    /// and return the reference to it.
+    ///
+    /// If the key is already present,
+    /// the [Policy] decides what to do and what to return.
+    pub fn insert(&self, key: K, value: V) -> P::Output<'_, K, V>
+    where
+        K: Hash + Eq,
+        P: Policy,
+    {
+        match self.try_insert(key, value) {
+            Ok(v) => P::inserted(v),
+            Err(duplicate) => P::duplicated(duplicate, |k, v| self.replace(k, v).0),
+        }
+    }
+    /// Push an item into the [PinnedIndexMap]
+    /// and return the reference to it,
+    /// or hand the item back if the key is already present,
+    /// regardless of the [Policy].
+    pub fn try_insert(&self, key: K, value: V) -> Result<&V, DuplicateKey<'_, K, V>>
+    where
+        K: Hash + Eq,
+    {
+        let mut guard = self.inner.write().expect(PANIC);
+        if let Some(i) = guard.find(&key) {
+            return Err(DuplicateKey::new(key, value, erase(&guard.entries[i].1)));
+        }
+        Ok(guard.push(key, value))
+    }
+    /// Push an item into the [PinnedIndexMap]
+    /// and return the reference to it,
+    /// together with the reference to the item it shadows, if any.
+    ///
+    /// The new item takes over the position of the shadowed one.
+    /// It always shadows, regardless of the [Policy].
+    pub fn replace(&self, key: K, value: V) -> (&V, Option<&V>)
+    where
+        K: Hash + Eq,
+    {
+        let mut guard = self.inner.write().expect(PANIC);
+        let guard = &mut *guard;
+        match guard.find(&key) {
+            Some(i) => {
+                let value = Box::pin(value);
+                let r = erase(&value);
+                let prev = mem::replace(&mut guard.entries[i].1, value);
+                let p = erase(&prev);
+                guard.shadowed.push(prev);
+                (r, Some(p))
+            }
+            None => (guard.push(key, value), None),
+        }
+    }
+    /// Get the number of shadowed items.
+    pub fn shadowed_len(&self) -> usize {
+        self.inner.read().expect(PANIC).shadowed.len()
+    }
+    /// Get an item in [PinnedIndexMap].
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q> + Hash + Eq,
+        Q: Hash + Eq + ?Sized,
+    {
+        let guard = self.inner.read().expect(PANIC);
+        let i = guard.find(key)?;
+        Some(erase(&guard.entries[i].1))
+    }
+    /// Get a key-value pair in [PinnedIndexMap] by its position.
+    pub fn get_index(&self, index: usize) -> Option<(&K, &V)> {
+        let guard = self.inner.read().expect(PANIC);
+        let (k, v) = guard.entries.get(index)?;
+        Some((erase(k), erase(v)))
+    }
+    /// Get the position of a key in [PinnedIndexMap].
+    pub fn get_index_of<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q> + Hash + Eq,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.read().expect(PANIC).find(key)
+    }
+    /// Get all keys in insertion order.
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        let guard = self.inner.read().expect(PANIC);
+        Keys::new(guard)
+    }
+    /// Get all values in insertion order.
+    pub fn values(&self) -> Values<'_, K, V> {
+        let guard = self.inner.read().expect(PANIC);
+        Values::new(guard)
+    }
+    /// Get an iterator over all items in insertion order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        IntoIterator::into_iter(self)
+    }
+}
+impl<'a, K, V, P> IntoIterator for &'a PinnedIndexMap<K, V, P> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        let guard = self.inner.read().expect(PANIC);
+        Iter::new(guard)
+    }
+}
+impl<K: Debug, V: Debug, P> Debug for PinnedIndexMap<K, V, P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+impl<K: Hash + Eq + Clone, V: Clone, P> Clone for PinnedIndexMap<K, V, P> {
+    fn clone(&self) -> Self {
+        let new = Self::default();
+        for (k, v) in self {
+            new.try_insert(k.clone(), v.clone())
+                .ok()
+                .expect("internal error: keys of a map are distinct");
+        }
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let v = PinnedIndexMap::new();
+        let a = v.insert(3, "a".to_owned());
+        let b = v.insert(1, "b".to_owned());
+        let c = v.insert(2, "c".to_owned());
+
+        assert_eq!(v.len(), 3);
+        assert!(v.contains_key(&1));
+        assert!(!v.contains_key(&4));
+        assert_eq!(v.get(&3), Some(a));
+        assert_eq!(v.get_index(1), Some((&1, b)));
+        assert_eq!(v.get_index(3), None);
+        assert_eq!(v.get_index_of(&2), Some(2));
+        assert_eq!(v.get_index_of(&4), None);
+
+        assert_eq!(v.keys().collect::<Vec<_>>(), [&3, &1, &2]);
+        assert_eq!(v.values().collect::<Vec<_>>(), [a, b, c]);
+        assert_eq!(v.iter().len(), 3);
+        assert_eq!(format!("{:?}", v), r#"{3: "a", 1: "b", 2: "c"}"#);
+        assert_eq!(format!("{:?}", v.clone()), format!("{:?}", v));
+    }
+
+    #[test]
+    fn stable_keys() {
+        let v = PinnedIndexMap::new();
+        let (k, _) = {
+            v.insert("0".to_owned(), 0);
+            v.get_index(0).unwrap()
+        };
+        for i in 1..64 {
+            v.insert(i.to_string(), i);
+        }
+        assert_eq!(
+            k as *const String,
+            v.get_index(0).unwrap().0 as *const String
+        );
+        assert_eq!(v.get(&"63".to_owned()), Some(&63));
+        assert_eq!(v.get("62"), Some(&62));
+        assert!(v.contains_key("0"));
+        assert_eq!(v.get_index_of("1"), Some(1));
+    }
+
+    #[test]
+    fn insert_duplicate() {
+        let v = PinnedIndexMap::new();
+        let a = v.insert(1, "a".to_owned());
+        v.insert(2, "b".to_owned());
+        let c = v.insert(1, "c".to_owned());
+        assert_eq!(v.get(&1), Some(c));
+        assert_eq!(v.get_index(0), Some((&1, c)));
+        assert_eq!(a, "a");
+        assert_eq!(v.len(), 2);
+        assert_eq!(v.shadowed_len(), 1);
+
+        let (d, prev) = v.replace(3, "d".to_owned());
+        assert_eq!((d.as_str(), prev), ("d", None));
+        assert_eq!(v.get_index(2), Some((&3, d)));
+
+        let v = PinnedIndexMap::with_policy(crate::KeepFirst);
+        let a = v.insert(1, 1);
+        let b = v.insert(1, 2);
+        assert_eq!(a as *const i32, b as *const i32);
+    }
+}
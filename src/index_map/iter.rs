@@ -0,0 +1,101 @@
+use std::{fmt::Debug, iter::FusedIterator, slice, sync::RwLockReadGuard};
+
+use super::{erase, Entry, Inner};
+
+macro_rules! iterator {
+    ($(#[$meta:meta])* $name:ident, $item:ty, |$k:ident, $v:ident| $map:expr) => {
+        $(#[$meta])*
+        pub struct $name<'a, K, V> {
+            /// Shall not be read. Only kept here to prevent the map from being modified.
+            #[allow(unused)]
+            guard: RwLockReadGuard<'a, Inner<K, V>>,
+            inner: slice::Iter<'a, Entry<K, V>>,
+        }
+
+        impl<'a, K, V> $name<'a, K, V> {
+            pub(super) fn new(guard: RwLockReadGuard<'a, Inner<K, V>>) -> Self {
+                let inner = unsafe {
+                    std::mem::transmute::<slice::Iter<'_, Entry<K, V>>, slice::Iter<'a, Entry<K, V>>>(
+                        guard.entries.iter(),
+                    )
+                };
+                Self { guard, inner }
+            }
+        }
+
+        impl<'a, K: 'a, V: 'a> Iterator for $name<'a, K, V> {
+            type Item = $item;
+
+            fn next(&mut self) -> Option<$item> {
+                self.inner.next().map(|($k, $v)| $map)
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.inner.size_hint()
+            }
+
+            fn last(mut self) -> Option<$item> {
+                self.next_back()
+            }
+        }
+
+        impl<'a, K: 'a, V: 'a> DoubleEndedIterator for $name<'a, K, V> {
+            fn next_back(&mut self) -> Option<$item> {
+                self.inner.next_back().map(|($k, $v)| $map)
+            }
+        }
+
+        impl<K, V> ExactSizeIterator for $name<'_, K, V> {
+            fn len(&self) -> usize {
+                self.inner.len()
+            }
+        }
+
+        impl<K, V> FusedIterator for $name<'_, K, V> {}
+    };
+}
+
+iterator!(
+    /// Iterator over key-value pairs of [super::PinnedIndexMap], in insertion order.
+    Iter,
+    (&'a K, &'a V),
+    |k, v| (erase(k), erase(v))
+);
+
+iterator!(
+    /// Iterator over keys of [super::PinnedIndexMap], in insertion order.
+    Keys,
+    &'a K,
+    |k, _v| erase(k)
+);
+
+iterator!(
+    /// Iterator over values of [super::PinnedIndexMap], in insertion order.
+    Values,
+    &'a V,
+    |_k, v| erase(v)
+);
+
+impl<K: Debug, V: Debug> Debug for Iter<'_, K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.inner.clone().map(|(k, v)| (&**k, &**v)))
+            .finish()
+    }
+}
+
+impl<K: Debug, V> Debug for Keys<'_, K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.inner.clone().map(|(k, _)| &**k))
+            .finish()
+    }
+}
+
+impl<K, V: Debug> Debug for Values<'_, K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.inner.clone().map(|(_, v)| &**v))
+            .finish()
+    }
+}
@@ -0,0 +1,310 @@
+//! A concurrent, deduplicating string interner.
+
+use super::PANIC;
+use alloc::boxed::Box;
+use core::{
+    hash::{BuildHasher, BuildHasherDefault, Hasher},
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    fmt::Debug,
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError},
+    thread,
+};
+
+/// A [Hasher] for keys that are hashes already.
+#[derive(Default)]
+struct Prehashed(u64);
+
+impl Hasher for Prehashed {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+    fn write(&mut self, _bytes: &[u8]) {
+        unreachable!("internal error: only `u64` can be hashed")
+    }
+    fn write_u64(&mut self, i: u64) {
+        self.0 = i;
+    }
+}
+
+/// Strings by their hashes.
+type Table = HashMap<u64, Vec<&'static str>, BuildHasherDefault<Prehashed>>;
+
+/// Strings whose hashes fall into the same shard,
+/// guarded by a single lock.
+#[derive(Default)]
+struct Shard {
+    table: Table,
+    /// Owns the strings in [Shard::table],
+    /// which are never dropped before the [Interner].
+    strings: Vec<Pin<Box<str>>>,
+}
+
+fn find(shard: &Shard, hash: u64, s: &str) -> Option<&'static str> {
+    shard.table.get(&hash)?.iter().find(|t| **t == s).copied()
+}
+
+/// Statistics of an [Interner].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InternerStats {
+    /// Lookups that found the string.
+    pub hits: u64,
+    /// Lookups that inserted the string.
+    pub misses: u64,
+    /// Times a lock was not immediately available,
+    /// or another thread inserted the same string between the read and the write.
+    pub contention: u64,
+}
+
+/// A deduplicating string interner, which can be shared between threads.
+///
+/// Strings are spread into shards by their hashes.
+/// Each lookup takes the read lock of its shard first,
+/// and only takes the write lock when the string is missing.
+///
+/// ```rust
+/// use pinned_bucket::*;
+/// let interner = Interner::new();
+/// let a = interner.intern("a");
+/// let b = interner.intern(&String::from("a"));
+/// assert_eq!(a as *const str, b as *const str);
+///
+/// let hash = interner.hash("c");
+/// let c = interner.intern_hashed(hash, "c");
+/// assert_eq!(interner.get_hashed(hash, "c"), Some(c));
+///
+/// let stats = interner.stats();
+/// assert_eq!((stats.hits, stats.misses), (1, 2));
+/// ```
+///
+/// As the strings inside are still managed by the interner,
+/// codes below won't compile.
+///
+/// ```compile_fail
+/// use pinned_bucket::*;
+/// let interner = Interner::new();
+/// let a = interner.intern("a");
+/// drop(interner);
+/// assert_eq!(a, "a");
+/// ```
+pub struct Interner<S = RandomState> {
+    shards: Box<[RwLock<Shard>]>,
+    hasher: S,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    contention: AtomicU64,
+}
+impl Default for Interner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Interner {
+    /// Create an empty [Interner] with a shard count
+    /// suited to the available parallelism.
+    pub fn new() -> Self {
+        let threads = thread::available_parallelism().map_or(1, usize::from);
+        Self::with_shards(threads * 4)
+    }
+    /// Create an empty [Interner] with at least `shards` shards.
+    pub fn with_shards(shards: usize) -> Self {
+        Self::with_shards_and_hasher(shards, RandomState::new())
+    }
+}
+impl<S: BuildHasher> Interner<S> {
+    /// Create an empty [Interner] with at least `shards` shards,
+    /// which hashes strings with `hasher`.
+    pub fn with_shards_and_hasher(shards: usize, hasher: S) -> Self {
+        let shards = shards.max(1).next_power_of_two();
+        Self {
+            shards: (0..shards).map(|_| RwLock::default()).collect(),
+            hasher,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            contention: AtomicU64::new(0),
+        }
+    }
+    /// Hash a string the way the [Interner] does.
+    pub fn hash(&self, s: &str) -> u64 {
+        self.hasher.hash_one(s)
+    }
+    fn shard(&self, hash: u64) -> &RwLock<Shard> {
+        // The lower bits are left for the table inside.
+        let i = (hash >> 32) as usize & (self.shards.len() - 1);
+        &self.shards[i]
+    }
+    fn read<'a>(&self, shard: &'a RwLock<Shard>) -> RwLockReadGuard<'a, Shard> {
+        match shard.try_read() {
+            Ok(guard) => guard,
+            Err(TryLockError::WouldBlock) => {
+                self.contention.fetch_add(1, Ordering::Relaxed);
+                shard.read().expect(PANIC)
+            }
+            Err(TryLockError::Poisoned(_)) => panic!("{}", PANIC),
+        }
+    }
+    fn write<'a>(&self, shard: &'a RwLock<Shard>) -> RwLockWriteGuard<'a, Shard> {
+        match shard.try_write() {
+            Ok(guard) => guard,
+            Err(TryLockError::WouldBlock) => {
+                self.contention.fetch_add(1, Ordering::Relaxed);
+                shard.write().expect(PANIC)
+            }
+            Err(TryLockError::Poisoned(_)) => panic!("{}", PANIC),
+        }
+    }
+    /// Get the interned copy of `s`, interning it if absent.
+    pub fn intern(&self, s: &str) -> &str {
+        self.intern_hashed(self.hash(s), s)
+    }
+    /// Get the interned copy of `s`, interning it if absent,
+    /// where `hash` is the precomputed [Interner::hash] of `s`.
+    ///
+    /// Passing a different hash is not unsafe,
+    /// but the same string might then be interned more than once.
+    pub fn intern_hashed(&self, hash: u64, s: &str) -> &str {
+        let shard = self.shard(hash);
+        if let Some(t) = find(&self.read(shard), hash, s) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return t;
+        }
+        let mut shard = self.write(shard);
+        // Someone else might have interned it before we got the lock.
+        if let Some(t) = find(&shard, hash, s) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            self.contention.fetch_add(1, Ordering::Relaxed);
+            return t;
+        }
+        let owned = Pin::new(Box::<str>::from(s));
+        // Lives as long as `shard.strings`, as the box is never dropped before it.
+        let t: &'static str = unsafe { core::mem::transmute::<&str, &str>(&owned) };
+        shard.strings.push(owned);
+        shard.table.entry(hash).or_default().push(t);
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        t
+    }
+    /// Get the interned copy of `s`, if any.
+    pub fn get(&self, s: &str) -> Option<&str> {
+        self.get_hashed(self.hash(s), s)
+    }
+    /// Get the interned copy of `s`, if any,
+    /// where `hash` is the precomputed [Interner::hash] of `s`.
+    pub fn get_hashed(&self, hash: u64, s: &str) -> Option<&str> {
+        find(&self.read(self.shard(hash)), hash, s)
+    }
+    /// Get the number of interned strings.
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|s| s.read().expect(PANIC).strings.len())
+            .sum()
+    }
+    /// Check if nothing is interned.
+    pub fn is_empty(&self) -> bool {
+        self.shards
+            .iter()
+            .all(|s| s.read().expect(PANIC).strings.is_empty())
+    }
+    /// Get the statistics so far.
+    pub fn stats(&self) -> InternerStats {
+        InternerStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            contention: self.contention.load(Ordering::Relaxed),
+        }
+    }
+}
+impl<S> Debug for Interner<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Interner")
+            .field("shards", &self.shards.len())
+            .field("hits", &self.hits)
+            .field("misses", &self.misses)
+            .field("contention", &self.contention)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let interner = Interner::with_shards(3);
+        assert_eq!(interner.shards.len(), 4);
+        assert!(interner.is_empty());
+        let a = interner.intern("a");
+        let b = interner.intern("b");
+        let a_ = interner.intern(&String::from("a"));
+        assert_eq!(a as *const str, a_ as *const str);
+        assert_ne!(a as *const str, b as *const str);
+        assert_eq!(interner.get("b"), Some(b));
+        assert_eq!(interner.get("c"), None);
+        assert_eq!(interner.len(), 2);
+        assert_eq!(
+            interner.stats(),
+            InternerStats {
+                hits: 1,
+                misses: 2,
+                contention: 0
+            }
+        );
+    }
+
+    #[test]
+    fn hash_collision() {
+        let interner = Interner::with_shards(1);
+        let a = interner.intern_hashed(0, "a");
+        let b = interner.intern_hashed(0, "b");
+        assert_eq!(a, "a");
+        assert_eq!(b, "b");
+        assert_eq!(
+            interner.intern_hashed(0, "a") as *const str,
+            a as *const str
+        );
+        assert_eq!(interner.get_hashed(0, "b"), Some(b));
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    fn stable_addresses() {
+        let interner = Interner::with_shards(1);
+        let first = interner.intern("0");
+        for i in 1..256 {
+            interner.intern(&i.to_string());
+        }
+        assert_eq!(
+            interner.get("0").unwrap() as *const str,
+            first as *const str
+        );
+        assert_eq!(interner.len(), 256);
+    }
+
+    #[test]
+    fn concurrent() {
+        let interner = Interner::with_shards(4);
+        let strings: Vec<String> = (0..1000).map(|i| (i % 100).to_string()).collect();
+        let interned: Vec<Vec<&str>> = thread::scope(|s| {
+            let workers: Vec<_> = strings
+                .chunks(250)
+                .map(|chunk| {
+                    let interner = &interner;
+                    s.spawn(move || chunk.iter().map(|s| interner.intern(s)).collect())
+                })
+                .collect();
+            workers.into_iter().map(|w| w.join().unwrap()).collect()
+        });
+        assert_eq!(interner.len(), 100);
+        for (s, i) in strings.iter().zip(interned.concat()) {
+            assert_eq!(s, i);
+            assert_eq!(interner.get(s).unwrap() as *const str, i as *const str);
+        }
+        let stats = interner.stats();
+        assert_eq!(stats.hits + stats.misses, 1000);
+        assert_eq!(stats.misses, 100);
+    }
+}
@@ -0,0 +1,185 @@
+use super::PinnedList;
+use alloc::boxed::Box;
+use core::{
+    mem::{self, ManuallyDrop},
+    ops::Index,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+static LEAKED_ITEMS: AtomicUsize = AtomicUsize::new(0);
+static LEAKED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+fn account(items: usize, bytes: usize) {
+    LEAKED_ITEMS.fetch_add(items, Ordering::Relaxed);
+    LEAKED_BYTES.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// How much has been leaked on purpose,
+/// through [PinnedList::leak] and [LeakyList].
+///
+/// Only the items themselves are counted,
+/// together with the [PinnedList] struct leaked by [PinnedList::leak].
+/// The buffers of the containers, which hold pointers to the items and grow with them,
+/// are not, and neither is the heap memory the items own.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LeakStats {
+    /// Number of leaked items.
+    pub items: usize,
+    /// Number of bytes taken by leaked items, and by leaked [PinnedList] structs.
+    pub bytes: usize,
+}
+
+/// Get how much has been leaked so far in this process.
+///
+/// ```rust
+/// use pinned_bucket::*;
+/// let before = leaked();
+/// let v = LeakyList::new();
+/// v.push(1u64);
+/// let after = leaked();
+/// assert!(after.items > before.items);
+/// assert!(after.bytes >= before.bytes + 8);
+/// ```
+pub fn leaked() -> LeakStats {
+    LeakStats {
+        items: LEAKED_ITEMS.load(Ordering::Relaxed),
+        bytes: LEAKED_BYTES.load(Ordering::Relaxed),
+    }
+}
+
+impl<T> PinnedList<T> {
+    /// Leak the [PinnedList], so that it and its items live forever.
+    ///
+    /// Items pushed afterwards are leaked as well, but not counted in [leaked].
+    /// Use [PinnedList::into_leaky] for that.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// let v = PinnedList::new();
+    /// v.push(1);
+    /// let v: &'static PinnedList<i32> = v.leak();
+    /// let a: &'static i32 = v.push(2);
+    /// assert_eq!(a, &2);
+    /// ```
+    pub fn leak(self) -> &'static Self
+    where
+        T: 'static,
+    {
+        account(
+            self.len(),
+            self.len() * mem::size_of::<T>() + mem::size_of::<Self>(),
+        );
+        Box::leak(Box::new(self))
+    }
+    /// Turn the [PinnedList] into a [LeakyList],
+    /// leaking the items inside.
+    pub fn into_leaky(self) -> LeakyList<T> {
+        account(self.len(), self.len() * mem::size_of::<T>());
+        LeakyList {
+            inner: ManuallyDrop::new(self),
+        }
+    }
+}
+
+/// A [PinnedList] that never drops its items,
+/// so references to them are `'static`.
+///
+/// Leaked items are counted in [leaked].
+///
+/// Only methods that keep the count right are exposed,
+/// so the [PinnedList] inside can't be reached.
+///
+/// ```rust
+/// use pinned_bucket::*;
+/// fn push(v: &LeakyList<String>) -> &'static str {
+///     v.push("a".to_owned())
+/// }
+/// let v = LeakyList::new();
+/// let a = push(&v);
+/// drop(v);
+/// assert_eq!(a, "a");
+/// ```
+#[derive(Debug, Default)]
+pub struct LeakyList<T> {
+    inner: ManuallyDrop<PinnedList<T>>,
+}
+
+impl<T> LeakyList<T> {
+    /// Create an empty [LeakyList].
+    pub const fn new() -> Self {
+        Self {
+            inner: ManuallyDrop::new(PinnedList::new()),
+        }
+    }
+    /// Push an item into the [LeakyList]
+    /// and return the reference to it.
+    pub fn push(&self, t: T) -> &'static T
+    where
+        T: 'static,
+    {
+        let r = self.inner.push(t);
+        account(1, mem::size_of::<T>());
+        // The item is never dropped.
+        unsafe { mem::transmute::<&T, &'static T>(r) }
+    }
+    /// Push a lot of items into the [LeakyList].
+    pub fn extend<U: IntoIterator<Item = T>, V: FromIterator<&'static T>>(&self, iter: U) -> V
+    where
+        T: 'static,
+    {
+        let refs: Vec<&T> = self.inner.extend(iter);
+        account(refs.len(), refs.len() * mem::size_of::<T>());
+        // Items are never dropped.
+        refs.into_iter()
+            .map(|r| unsafe { mem::transmute::<&T, &'static T>(r) })
+            .collect()
+    }
+    /// Get the number of elements in [LeakyList].
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+    /// Check if the [LeakyList] is empty.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+    /// Get an item in [LeakyList].
+    pub fn get(&self, index: usize) -> Option<&'static T>
+    where
+        T: 'static,
+    {
+        let r = self.inner.get(index)?;
+        // The item is never dropped.
+        Some(unsafe { mem::transmute::<&T, &'static T>(r) })
+    }
+}
+
+impl<T> Index<usize> for LeakyList<T> {
+    type Output = T;
+    fn index(&self, index: usize) -> &T {
+        &self.inner[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn into_leaky() {
+        let v = PinnedList::new();
+        let a = v.push(1u32) as *const u32;
+        let before = leaked();
+        let v = v.into_leaky();
+        let b = v.push(2u32);
+        let c: Vec<_> = v.extend([3u32, 4]);
+        let after = leaked();
+        // Other tests may leak at the same time.
+        assert!(after.items >= before.items + 4);
+        assert!(after.bytes >= before.bytes + 16);
+        assert_eq!(&v[0] as *const u32, a);
+        assert_eq!(b, &2);
+        assert_eq!(c, [&3, &4]);
+        assert_eq!(v.get(3), Some(&4));
+        assert_eq!(v.len(), 4);
+    }
+}
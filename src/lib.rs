@@ -1,15 +1,54 @@
 //! Mutable containers for pinned and immutable items.
 //!
 //! A substitute for [Box::leak](https://doc.rust-lang.org/stable/alloc/boxed/struct.Box.html#method.leak).
+//!
+//! Most containers can be constructed in `const` contexts,
+//! so a global registry is just a `static`,
+//! and references into it are `'static`:
+//!
+//! ```rust
+//! use pinned_bucket::*;
+//!
+//! static REGISTRY: PinnedMap<&str, String> = PinnedMap::new();
+//!
+//! fn register(name: &'static str, description: &str) -> &'static String {
+//!     REGISTRY.insert(name, description.to_owned())
+//! }
+//!
+//! let a = register("a", "the first one");
+//! assert_eq!(REGISTRY.get("a"), Some(a));
+//! ```
 
 #![warn(missing_docs, rust_2021_compatibility, rust_2018_idioms)]
 
 extern crate alloc;
 
-const PANIC: &'static str = "Another thread panicked while holding the lock.";
+const PANIC: &str = "Another thread panicked while holding the lock.";
 
+mod arc_ref;
+mod checkpoint;
+pub mod index_map;
+mod interner;
+mod leak;
 mod list;
 mod map;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+mod multimap;
+mod notify;
+pub mod policy;
+#[cfg(feature = "wal")]
+pub mod wal;
 
-pub use list::PinnedList;
-pub use map::{Iter, Keys, PinnedMap, Values};
+pub use arc_ref::{ArcRef, WeakRef};
+pub use checkpoint::{Checkpoint, RollbackError};
+pub use index_map::PinnedIndexMap;
+pub use interner::{Interner, InternerStats};
+pub use leak::{leaked, LeakStats, LeakyList};
+pub use list::{Cursor, FrozenPinnedList, PinnedList, ScopedList};
+pub use map::{
+    Entry, Event, FrozenPinnedMap, Iter, Keys, OccupiedEntry, PinnedKeyMap, PinnedMap, Shadowed,
+    Subscription, VacantEntry, Values,
+};
+pub use multimap::{GetAll, PinnedMultiMap};
+pub use policy::{DuplicateKey, KeepFirst, Panic, Policy, ReturnError, Shadow};
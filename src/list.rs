@@ -1,11 +1,22 @@
-use super::PANIC;
-use alloc::{boxed::Box, vec::Vec};
+use super::{checkpoint::Issuer, notify::Notify, ArcRef, Checkpoint, RollbackError, PANIC};
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
 use core::{
     mem,
     ops::{Deref, Index},
     pin::Pin,
+    time::Duration,
 };
-use std::sync::RwLock;
+use std::{fmt::Debug, sync::RwLock};
+
+pub use cursor::Cursor;
+pub use frozen::FrozenPinnedList;
+pub use scope::ScopedList;
+
+mod cursor;
+mod frozen;
+#[cfg(feature = "rayon")]
+mod par;
+mod scope;
 
 /// A list of `Pin<Box<T>>`.
 ///
@@ -39,26 +50,40 @@ use std::sync::RwLock;
 /// If you [clone](Clone::clone) this,
 /// references to items in new container will be different to
 /// references to those in old container.
-#[derive(Debug)]
 pub struct PinnedList<T> {
     sections: RwLock<Vec<Pin<Box<T>>>>,
+    issuer: Issuer,
+    notify: Notify,
 }
 impl<T> Default for PinnedList<T> {
     fn default() -> Self {
-        Self {
-            sections: RwLock::new(Vec::new()),
-        }
+        Self::new()
     }
 }
 impl<T> PinnedList<T> {
     /// Create an empty [PinnedList].
-    pub fn new() -> Self {
-        Self::default()
+    ///
+    /// It is `const`, so it can be put in a `static` directly.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// static LIST: PinnedList<i32> = PinnedList::new();
+    /// let a: &'static i32 = LIST.push(1);
+    /// assert_eq!(a, &1);
+    /// ```
+    pub const fn new() -> Self {
+        Self {
+            sections: RwLock::new(Vec::new()),
+            issuer: Issuer::new(),
+            notify: Notify::new(),
+        }
     }
     /// Create a [PinnedList] with given capacity.
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
             sections: Vec::with_capacity(capacity).into(),
+            issuer: Issuer::new(),
+            notify: Notify::new(),
         }
     }
     /// Get current capacity.
@@ -69,6 +94,10 @@ impl<T> PinnedList<T> {
     pub fn len(&self) -> usize {
         self.sections.read().expect(PANIC).len()
     }
+    /// Check if the [PinnedList] is empty.
+    pub fn is_empty(&self) -> bool {
+        self.sections.read().expect(PANIC).is_empty()
+    }
     /// Push an item into the [PinnedList]
     /// and return the reference to it.
     pub fn push(&self, t: T) -> &T {
@@ -76,21 +105,124 @@ impl<T> PinnedList<T> {
         let r = item.deref();
         let r: &T = unsafe { mem::transmute::<&T, &T>(r) };
         self.sections.write().expect(PANIC).push(item);
+        self.notify.notify();
         r
     }
+    /// Get an item in [PinnedList].
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let sec = self.sections.read().expect(PANIC);
+        let r = sec.get(index)?.deref();
+        Some(unsafe { mem::transmute::<&T, &T>(r) })
+    }
+    /// Block until the [PinnedList] has at least `len` items,
+    /// and return the number of items then.
+    pub fn wait_len(&self, len: usize) -> usize {
+        self.wait_len_until(len, None)
+            .expect("internal error: waited without timeout")
+    }
+    /// Block until the [PinnedList] has at least `len` items,
+    /// or `timeout` elapses.
+    pub fn wait_len_timeout(&self, len: usize, timeout: Duration) -> Option<usize> {
+        self.wait_len_until(len, Some(timeout))
+    }
+    fn wait_len_until(&self, len: usize, timeout: Option<Duration>) -> Option<usize> {
+        let enough = || Some(self.len()).filter(|l| *l >= len);
+        self.notify.wait(enough, timeout)
+    }
+    /// Block until there is an item at `index`,
+    /// and return the reference to it.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// use std::{thread, time::Duration};
+    /// let v = PinnedList::new();
+    /// thread::scope(|s| {
+    ///     s.spawn(|| assert_eq!(v.wait_index(1), &2));
+    ///     v.push(1);
+    ///     v.push(2);
+    /// });
+    /// assert_eq!(v.wait_index_timeout(2, Duration::from_millis(10)), None);
+    /// ```
+    pub fn wait_index(&self, index: usize) -> &T {
+        self.notify
+            .wait(|| self.get(index), None)
+            .expect("internal error: waited without timeout")
+    }
+    /// Block until there is an item at `index`, or `timeout` elapses.
+    pub fn wait_index_timeout(&self, index: usize, timeout: Duration) -> Option<&T> {
+        self.notify.wait(|| self.get(index), Some(timeout))
+    }
+    /// Push an item into the [PinnedList] behind an [Arc]
+    /// and return an [ArcRef] to it.
+    pub fn push_arc(self: &Arc<Self>, t: T) -> ArcRef<T>
+    where
+        T: Send + Sync + 'static,
+    {
+        ArcRef::new(self, |list| list.push(t))
+    }
+    /// Push the item produced by `f` into the [PinnedList]
+    /// and return the reference to it.
+    ///
+    /// `f` runs without holding the lock.
+    /// Nothing is pushed if it fails, and the error is returned as is.
+    pub fn try_push_with<E>(&self, f: impl FnOnce() -> Result<T, E>) -> Result<&T, E> {
+        Ok(self.push(f()?))
+    }
+    /// Remember the current length of the [PinnedList],
+    /// so that it can be [rolled back](PinnedList::rollback) to.
+    pub fn checkpoint(&self) -> Checkpoint {
+        self.issuer.checkpoint(self.len() as u64)
+    }
+    /// Drop all items pushed after `checkpoint`.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// let mut v = PinnedList::new();
+    /// v.push(1);
+    /// let checkpoint = v.checkpoint();
+    /// v.push(2);
+    /// v.rollback(checkpoint).unwrap();
+    /// assert_eq!(v.len(), 1);
+    /// ```
+    ///
+    /// Nothing is dropped if `checkpoint` was taken on another container,
+    /// or after a point the container has since been rolled back to.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// let v = PinnedList::<i32>::new();
+    /// let mut w = PinnedList::<i32>::new();
+    /// assert_eq!(w.rollback(v.checkpoint()), Err(RollbackError::Foreign));
+    /// ```
+    pub fn rollback(&mut self, checkpoint: Checkpoint) -> Result<(), RollbackError> {
+        let len = self.issuer.rollback(checkpoint)?;
+        let sections = self.sections.get_mut().expect(PANIC);
+        sections.truncate(len as usize);
+        Ok(())
+    }
     /// Push a lot of items into the [PinnedList].
     pub fn extend<'s, U: IntoIterator<Item = T>, V: FromIterator<&'s T>>(&'s self, iter: U) -> V {
         let mut sec = self.sections.write().expect(PANIC);
         let len = sec.len();
         sec.extend(iter.into_iter().map(|item| Box::pin(item)));
-        sec[len..]
+        let refs = sec[len..]
             .iter()
             .map(|item| {
                 let r = item.deref();
                 let r: &'s T = unsafe { mem::transmute::<&T, &T>(r) };
                 r
             })
-            .collect()
+            .collect();
+        drop(sec);
+        self.notify.notify();
+        refs
+    }
+}
+impl<T: Debug> Debug for PinnedList<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PinnedList")
+            .field("sections", &self.sections)
+            .finish()
     }
 }
 impl<T, I> Index<I> for PinnedList<T>
@@ -108,7 +240,11 @@ impl<T: Clone> Clone for PinnedList<T> {
     fn clone(&self) -> Self {
         let values = self.sections.read().expect(PANIC);
         let sections = values.clone().into();
-        Self { sections }
+        Self {
+            sections,
+            issuer: Issuer::new(),
+            notify: Notify::new(),
+        }
     }
 }
 
@@ -128,6 +264,7 @@ mod tests {
 
     /// To ensure that allocated items won't be moved.
     #[test]
+    #[allow(clippy::useless_conversion)]
     fn resize() {
         let v = PinnedList::with_capacity(4);
         let cap = v.capacity();
@@ -145,6 +282,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_conversion)]
     fn extend_resize() {
         let v: PinnedList<usize> = PinnedList::with_capacity(4);
         let former: Vec<_> = v.extend((0..4).into_iter());
@@ -157,10 +295,100 @@ mod tests {
     }
 
     #[test]
+    fn try_push_with() {
+        let v = PinnedList::new();
+        assert_eq!(v.try_push_with(|| "x".parse::<i32>()).ok(), None);
+        assert!(v.is_empty());
+        let a = v.try_push_with(|| "1".parse::<i32>()).unwrap();
+        assert_eq!(a, &1);
+        assert_eq!(a as *const i32, &v[0] as *const i32);
+        assert_eq!(v.len(), 1);
+    }
+
+    #[test]
+    #[allow(clippy::useless_conversion)]
     fn debug_list() {
         let v: PinnedList<usize> = PinnedList::with_capacity(2);
         let _: Vec<_> = v.extend((0..4).into_iter());
         let u = v.clone();
         assert_eq!(format!("{:?}", v), format!("{:?}", u));
     }
+
+    #[test]
+    fn rollback() {
+        let mut v = PinnedList::new();
+        v.push(1);
+        let first = v.checkpoint();
+        v.push(2);
+        let second = v.checkpoint();
+        v.push(3);
+        v.rollback(second).unwrap();
+        assert_eq!(v.len(), 2);
+        v.rollback(first).unwrap();
+        assert_eq!(v.len(), 1);
+        // Checkpoints can be reused.
+        v.push(4);
+        v.rollback(first).unwrap();
+        assert_eq!(v.len(), 1);
+    }
+
+    #[test]
+    fn rollback_stale() {
+        let mut v = PinnedList::new();
+        let first = v.checkpoint();
+        v.push(1);
+        let second = v.checkpoint();
+        v.rollback(first).unwrap();
+        v.extend::<_, Vec<_>>([2, 3, 4]);
+        assert_eq!(v.rollback(second), Err(RollbackError::Discarded));
+        assert_eq!(v.len(), 3);
+        let w = v.clone();
+        assert_eq!(v.rollback(w.checkpoint()), Err(RollbackError::Foreign));
+        assert_eq!(
+            RollbackError::Foreign.to_string(),
+            "checkpoint taken on another container"
+        );
+    }
+
+    #[test]
+    fn debug_items_only() {
+        let v = PinnedList::new();
+        v.push(1);
+        v.checkpoint();
+        assert_eq!(format!("{:?}", v), format!("{:?}", v.clone()));
+        assert_eq!(
+            format!("{:?}", v),
+            "PinnedList { sections: RwLock { data: [1], poisoned: false, .. } }"
+        );
+    }
+
+    #[test]
+    fn cursor_clone() {
+        struct NotClone(i32);
+        let v = PinnedList::new();
+        let mut a = v.cursor();
+        v.push(NotClone(1));
+        let mut b = a.clone();
+        assert_eq!(a.next_batch().map(|n| n.0).collect::<Vec<_>>(), [1]);
+        v.push(NotClone(2));
+        assert_eq!(b.next_batch().map(|n| n.0).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(a.clone().position(), 1);
+    }
+
+    #[test]
+    fn scope() {
+        use std::rc::Rc;
+        let rc = Rc::new(());
+        let v = PinnedList::new();
+        v.push(rc.clone());
+        v.scope(|sub| {
+            sub.push(rc.clone());
+            sub.scope(|sub| {
+                sub.push(rc.clone());
+            });
+            assert_eq!(Rc::strong_count(&rc), 3);
+        });
+        assert_eq!(Rc::strong_count(&rc), 2);
+        assert_eq!(v.len(), 1);
+    }
 }
@@ -0,0 +1,135 @@
+use super::PinnedList;
+#[cfg(feature = "async")]
+use core::future::poll_fn;
+use core::time::Duration;
+
+/// A reader of a [PinnedList] used as an append-only log,
+/// which remembers how far it has read.
+///
+/// Any number of cursors can read the same [PinnedList],
+/// and a clone continues from the same position independently.
+/// As the items are pinned,
+/// references to them can be kept as long as the [PinnedList] lives.
+///
+/// ```rust
+/// use pinned_bucket::*;
+/// let v = PinnedList::new();
+/// let mut a = v.cursor();
+/// v.push(1);
+/// v.push(2);
+/// let mut b = a.clone();
+/// assert_eq!(a.next_batch().collect::<Vec<_>>(), [&1, &2]);
+/// v.push(3);
+/// assert_eq!(a.next_batch().collect::<Vec<_>>(), [&3]);
+/// assert_eq!(a.next_batch().count(), 0);
+/// assert_eq!(b.next_batch().collect::<Vec<_>>(), [&1, &2, &3]);
+/// ```
+#[derive(Debug)]
+pub struct Cursor<'a, T> {
+    list: &'a PinnedList<T>,
+    pos: usize,
+}
+
+// Not derived, which would require `T: Clone`.
+impl<T> Clone for Cursor<'_, T> {
+    fn clone(&self) -> Self {
+        Self {
+            list: self.list,
+            pos: self.pos,
+        }
+    }
+}
+
+impl<T> PinnedList<T> {
+    /// Create a [Cursor] starting at the first item.
+    pub fn cursor(&self) -> Cursor<'_, T> {
+        self.cursor_at(0)
+    }
+    /// Create a [Cursor] starting at `index`,
+    /// for example [PinnedList::len] to skip the items so far.
+    pub fn cursor_at(&self, index: usize) -> Cursor<'_, T> {
+        Cursor {
+            list: self,
+            pos: index,
+        }
+    }
+}
+
+impl<'a, T> Cursor<'a, T> {
+    /// Get the index of the next item to read.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+    /// Get the [PinnedList] read by the [Cursor].
+    pub fn list(&self) -> &'a PinnedList<T> {
+        self.list
+    }
+    /// Get the items pushed since the last call,
+    /// which may be none.
+    ///
+    /// Items pushed while iterating are left for the next call.
+    pub fn next_batch(&mut self) -> impl ExactSizeIterator<Item = &'a T> + 'a {
+        let list = self.list;
+        let start = self.pos;
+        self.pos = self.pos.max(list.len());
+        (start..self.pos).map(move |i| &list[i])
+    }
+    /// Block until there are new items, and get them.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// use std::thread;
+    /// let v = PinnedList::new();
+    /// thread::scope(|s| {
+    ///     let mut c = v.cursor();
+    ///     s.spawn(move || {
+    ///         let mut sum = 0;
+    ///         while sum < 6 {
+    ///             sum += c.wait_batch().sum::<i32>();
+    ///         }
+    ///         assert_eq!(sum, 6);
+    ///     });
+    ///     v.push(1);
+    ///     v.push(2);
+    ///     v.push(3);
+    /// });
+    /// ```
+    pub fn wait_batch(&mut self) -> impl ExactSizeIterator<Item = &'a T> + 'a {
+        self.list.wait_len(self.pos + 1);
+        self.next_batch()
+    }
+    /// Block until there are new items, or `timeout` elapses.
+    ///
+    /// On timeout, the returned iterator is empty.
+    pub fn wait_batch_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> impl ExactSizeIterator<Item = &'a T> + 'a {
+        self.list.wait_len_timeout(self.pos + 1, timeout);
+        self.next_batch()
+    }
+    /// Wait until there are new items, and get them.
+    ///
+    /// It does not rely on any particular executor.
+    ///
+    /// ```rust
+    /// # async fn f() {
+    /// use pinned_bucket::*;
+    /// let v = PinnedList::new();
+    /// let mut c = v.cursor();
+    /// v.push(1);
+    /// assert_eq!(c.next_batch_async().await.collect::<Vec<_>>(), [&1]);
+    /// # }
+    /// ```
+    #[cfg(feature = "async")]
+    pub async fn next_batch_async(&mut self) -> impl ExactSizeIterator<Item = &'a T> + 'a {
+        let list = self.list;
+        let len = self.pos + 1;
+        poll_fn(|cx| {
+            let enough = || (list.len() >= len).then_some(());
+            list.notify.poll_wait(cx, enough)
+        })
+        .await;
+        self.next_batch()
+    }
+}
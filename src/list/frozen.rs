@@ -0,0 +1,104 @@
+use super::PinnedList;
+use crate::PANIC;
+use alloc::{boxed::Box, vec::Vec};
+use core::{fmt::Debug, ops::Index, pin::Pin};
+use std::sync::RwLockReadGuard;
+
+/// A read-only view of a [PinnedList] without a lock on each access.
+///
+/// Constructed by [PinnedList::freeze].
+/// It keeps the [PinnedList] read-locked until dropped,
+/// so pushing to it meanwhile blocks,
+/// and deadlocks if done on the same thread.
+pub struct FrozenPinnedList<'a, T> {
+    guard: RwLockReadGuard<'a, Vec<Pin<Box<T>>>>,
+}
+
+impl<T> PinnedList<T> {
+    /// Give up pushing for a while, and read without taking the lock every time.
+    ///
+    /// It only borrows the [PinnedList],
+    /// so references handed out before stay valid.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// let v = PinnedList::new();
+    /// let a = v.push(1);
+    /// v.push(2);
+    /// let frozen = v.freeze();
+    /// assert_eq!(&frozen[0] as *const i32, a as *const i32);
+    /// assert_eq!(frozen.iter().collect::<Vec<_>>(), [&1, &2]);
+    /// drop(frozen);
+    /// v.push(3);
+    /// assert_eq!(a, &1);
+    /// ```
+    pub fn freeze(&self) -> FrozenPinnedList<'_, T> {
+        FrozenPinnedList {
+            guard: self.sections.read().expect(PANIC),
+        }
+    }
+}
+
+impl<T> FrozenPinnedList<'_, T> {
+    /// Get the number of elements in [FrozenPinnedList].
+    pub fn len(&self) -> usize {
+        self.guard.len()
+    }
+    /// Check if the [FrozenPinnedList] is empty.
+    pub fn is_empty(&self) -> bool {
+        self.guard.is_empty()
+    }
+    /// Get an item in [FrozenPinnedList].
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.guard.get(index).map(|item| &**item)
+    }
+    /// Get an iterator over all items.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> + ExactSizeIterator {
+        self.guard.iter().map(|item| &**item)
+    }
+}
+
+impl<T, I> Index<I> for FrozenPinnedList<'_, T>
+where
+    Vec<Pin<Box<T>>>: Index<I, Output = Pin<Box<T>>>,
+{
+    type Output = T;
+    fn index(&self, index: I) -> &Self::Output {
+        &self.guard[index]
+    }
+}
+
+impl<T: Debug> Debug for FrozenPinnedList<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn freeze() {
+        let list = PinnedList::new();
+        let a = list.push(1);
+        list.push(2);
+        let frozen = list.freeze();
+        assert_eq!(frozen.len(), 2);
+        assert_eq!(frozen.get(2), None);
+        assert_eq!(format!("{:?}", frozen), "[1, 2]");
+        thread::scope(|s| {
+            for i in 0..2 {
+                let frozen = &frozen;
+                s.spawn(move || assert_eq!(frozen[i], i as i32 + 1));
+            }
+        });
+        // References handed out before the freeze are still usable.
+        assert_eq!(a, &frozen[0]);
+        drop(frozen);
+        list.push(3);
+        assert_eq!(a, &1);
+        assert_eq!(list.len(), 3);
+    }
+}
@@ -0,0 +1,70 @@
+use super::PinnedList;
+use crate::PANIC;
+use alloc::{boxed::Box, vec::Vec};
+use core::{mem, ops::Deref};
+use rayon::{
+    iter::{FromParallelIterator, IntoParallelIterator, ParallelExtend, ParallelIterator},
+    vec::IntoIter,
+};
+
+impl<T: Sync> PinnedList<T> {
+    /// Get a parallel iterator over all items.
+    ///
+    /// It iterates over the items present when called,
+    /// and doesn't hold the lock afterwards.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// use rayon::prelude::*;
+    /// let v = PinnedList::new();
+    /// v.extend::<_, Vec<_>>([1, 2, 3]);
+    /// assert_eq!(v.par_iter().sum::<i32>(), 6);
+    /// ```
+    pub fn par_iter(&self) -> IntoIter<&T> {
+        let sections = self.sections.read().expect(PANIC);
+        let refs: Vec<&T> = sections
+            .iter()
+            .map(|item| {
+                let r = item.deref();
+                unsafe { mem::transmute::<&T, &T>(r) }
+            })
+            .collect();
+        refs.into_par_iter()
+    }
+}
+
+impl<'a, T: Sync> IntoParallelIterator for &'a PinnedList<T> {
+    type Iter = IntoIter<&'a T>;
+    type Item = &'a T;
+    fn into_par_iter(self) -> Self::Iter {
+        self.par_iter()
+    }
+}
+
+impl<T: Send> FromParallelIterator<T> for PinnedList<T> {
+    fn from_par_iter<I: IntoParallelIterator<Item = T>>(par_iter: I) -> Self {
+        let list = Self::new();
+        (&list).par_extend(par_iter);
+        list
+    }
+}
+
+impl<T: Send> ParallelExtend<T> for PinnedList<T> {
+    fn par_extend<I: IntoParallelIterator<Item = T>>(&mut self, par_iter: I) {
+        (&*self).par_extend(par_iter)
+    }
+}
+
+impl<T: Send> ParallelExtend<T> for &PinnedList<T> {
+    fn par_extend<I: IntoParallelIterator<Item = T>>(&mut self, par_iter: I) {
+        // Collected on each thread first, so that the lock is taken only once.
+        let chunks = par_iter.into_par_iter().collect_vec_list();
+        let mut sections = self.sections.write().expect(PANIC);
+        sections.reserve(chunks.iter().map(Vec::len).sum());
+        for chunk in chunks {
+            sections.extend(chunk.into_iter().map(Box::pin));
+        }
+        drop(sections);
+        self.notify.notify();
+    }
+}
@@ -0,0 +1,62 @@
+use super::PinnedList;
+use core::ops::Deref;
+
+/// A child container of a [PinnedList],
+/// whose items are dropped at the end of [PinnedList::scope].
+///
+/// It dereferences to its own [PinnedList],
+/// so it can be pushed into and scoped again.
+#[derive(Debug)]
+pub struct ScopedList<'p, T> {
+    parent: &'p PinnedList<T>,
+    items: PinnedList<T>,
+}
+
+impl<T> PinnedList<T> {
+    /// Run `f` with a child container,
+    /// whose items are dropped when `f` returns.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// let v = PinnedList::new();
+    /// let a = v.push(1);
+    /// let sum = v.scope(|sub| {
+    ///     let b = sub.push(*a + 1);
+    ///     let c = sub.scope(|sub2| *sub2.push(*b + 1) + *sub2.parent().push(3));
+    ///     assert_eq!(sub.len(), 2);
+    ///     *a + *b + c
+    /// });
+    /// assert_eq!(sum, 9);
+    /// assert_eq!(v.len(), 1);
+    /// ```
+    ///
+    /// References to items in the child container can't escape.
+    ///
+    /// ```compile_fail
+    /// use pinned_bucket::*;
+    /// let v = PinnedList::new();
+    /// let a = v.scope(|sub| sub.push(1));
+    /// assert_eq!(a, &1);
+    /// ```
+    pub fn scope<R>(&self, f: impl FnOnce(&ScopedList<'_, T>) -> R) -> R {
+        let sub = ScopedList {
+            parent: self,
+            items: PinnedList::new(),
+        };
+        f(&sub)
+    }
+}
+
+impl<'p, T> ScopedList<'p, T> {
+    /// Get the container this one is scoped in.
+    pub fn parent(&self) -> &'p PinnedList<T> {
+        self.parent
+    }
+}
+
+impl<T> Deref for ScopedList<'_, T> {
+    type Target = PinnedList<T>;
+    fn deref(&self) -> &PinnedList<T> {
+        &self.items
+    }
+}
@@ -1,19 +1,89 @@
 use super::PANIC;
+use crate::{
+    checkpoint::Issuer,
+    notify::Notify,
+    policy::{DuplicateKey, Policy, Shadow},
+    ArcRef, Checkpoint, RollbackError,
+};
 use alloc::boxed::Box;
-use core::{mem, ops::Deref, pin::Pin};
-use std::{collections::BTreeMap, fmt::Debug, sync::RwLock};
+use alloc::sync::Arc;
+#[cfg(feature = "async")]
+use core::future::{poll_fn, Future};
+use core::{
+    borrow::Borrow,
+    convert::Infallible,
+    marker::PhantomData,
+    mem,
+    ops::Deref,
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use pending::Claim;
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    sync::{Mutex, RwLock},
+};
 
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use frozen::FrozenPinnedMap;
 pub use iter::Iter;
+pub use key_map::PinnedKeyMap;
 pub use keys::Keys;
+pub use shadowed::Shadowed;
+pub use subscribe::{Event, Subscription};
 pub use values::Values;
 
+mod entry;
+mod frozen;
 mod iter;
+mod key_map;
 mod keys;
+#[cfg(feature = "rayon")]
+mod par;
+mod pending;
+mod shadowed;
+mod subscribe;
 mod values;
 
-fn erase<V>(v: &Pin<Box<V>>) -> &V {
+fn erase<P: Deref>(v: &P) -> &P::Target
+where
+    P::Target: Sized,
+{
     let r = v.deref();
-    unsafe { mem::transmute::<&V, &V>(r) }
+    unsafe { mem::transmute::<&P::Target, &P::Target>(r) }
+}
+
+/// A pinned item with the sequence number of its insertion.
+#[derive(Clone)]
+struct Slot<V> {
+    seq: u64,
+    value: Pin<Box<V>>,
+}
+
+impl<V> Slot<V> {
+    /// Shall be called under the write lock,
+    /// so that sequence numbers follow the order of insertion.
+    fn new(value: V, next_seq: &AtomicU64) -> Self {
+        Self {
+            seq: next_seq.fetch_add(1, Ordering::Relaxed),
+            value: Box::pin(value),
+        }
+    }
+}
+
+impl<V> Deref for Slot<V> {
+    type Target = V;
+    fn deref(&self) -> &V {
+        &self.value
+    }
+}
+
+impl<V: Debug> Debug for Slot<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.value, f)
+    }
 }
 
 /// A map from `K` to `Pin<Box<V>>`.
@@ -60,27 +130,100 @@ fn erase<V>(v: &Pin<Box<V>>) -> &V {
 /// references to items in new container will be different to
 /// references to those in old container.
 ///
-/// In `strict` mode, the container will panic if you try to
-/// insert an item with the same key.
-#[derive(Debug)]
-pub struct PinnedMap<K, V> {
-    sections: RwLock<BTreeMap<K, Pin<Box<V>>>>,
-    #[cfg(not(feature = "strict"))]
-    shadowed: RwLock<Vec<Pin<Box<V>>>>,
+/// What happens when inserting an item with a key already present
+/// is decided by the [Policy] `P`.
+/// By default, the previous item is [shadowed](Shadow) but kept alive,
+/// see [PinnedMap::replace] and [PinnedMap::history].
+///
+/// ```rust
+/// use pinned_bucket::*;
+/// let v = PinnedMap::with_policy(KeepFirst);
+/// let a = v.insert(1, 2);
+/// let b = v.insert(1, 3);
+/// assert_eq!(a as *const i32, b as *const i32);
+///
+/// let v = PinnedMap::with_policy(ReturnError);
+/// let a = v.insert(1, 2).unwrap();
+/// let e = v.insert(1, 3).unwrap_err();
+/// assert_eq!(e.existing(), a);
+/// assert_eq!(e.into_inner(), (1, 3));
+/// ```
+pub struct PinnedMap<K, V, P = Shadow> {
+    sections: RwLock<BTreeMap<K, Slot<V>>>,
+    /// Shadowed items with their keys, oldest first.
+    shadowed: RwLock<Vec<shadowed::Item<K, V>>>,
+    /// Keys whose initializers are running outside the lock.
+    pending: Mutex<pending::List<K>>,
+    /// Sequence number of the next inserted item.
+    next_seq: AtomicU64,
+    issuer: Issuer,
+    /// Wakes up [PinnedMap::wait_get] callers.
+    notify: Notify,
+    /// Callbacks registered by [PinnedMap::subscribe].
+    subscribers: Mutex<subscribe::Subscribers<K, V>>,
+    policy: PhantomData<fn() -> P>,
 }
-impl<K, V> Default for PinnedMap<K, V> {
+// Send and Sync under the same bounds as a `BTreeMap<K, V>`,
+// so `PinnedMap<Cell<u8>, u8>` stays `Send`.
+#[allow(dead_code)]
+const _: () = {
+    fn send<T: Send>() {}
+    fn sync<T: Sync>() {}
+    fn check<K: Send, V: Send, L: Send + Sync, W: Send + Sync, P>() {
+        send::<PinnedMap<K, V, P>>();
+        sync::<PinnedMap<L, W, P>>();
+    }
+};
+impl<K, V, P> Default for PinnedMap<K, V, P> {
     fn default() -> Self {
+        Self::empty()
+    }
+}
+impl<K, V> PinnedMap<K, V> {
+    /// Create an empty [PinnedMap] with the default [Shadow] policy.
+    ///
+    /// It is `const`, so it can be put in a `static` directly.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// static MAP: PinnedMap<&str, i32> = PinnedMap::new();
+    /// let a: &'static i32 = MAP.insert("a", 1);
+    /// assert_eq!(MAP.get("a"), Some(a));
+    /// ```
+    pub const fn new() -> Self {
+        Self::empty()
+    }
+}
+impl<K, V, P> PinnedMap<K, V, P> {
+    const fn empty() -> Self {
         Self {
             sections: RwLock::new(BTreeMap::new()),
-            #[cfg(not(feature = "strict"))]
             shadowed: RwLock::new(Vec::new()),
+            pending: Mutex::new(Vec::new()),
+            next_seq: AtomicU64::new(0),
+            issuer: Issuer::new(),
+            notify: Notify::new(),
+            subscribers: Mutex::new(subscribe::Subscribers::new()),
+            policy: PhantomData,
         }
     }
-}
-impl<K, V> PinnedMap<K, V> {
-    /// Create an empty [PinnedMap].
-    pub fn new() -> Self {
-        Self::default()
+    /// Create an empty [PinnedMap] with given [Policy].
+    ///
+    /// Only the type of `policy` matters,
+    /// so it shall be [Copy], which also means it has nothing to drop.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// static MAP: PinnedMap<i32, i32, KeepFirst> = PinnedMap::with_policy(KeepFirst);
+    /// assert_eq!(MAP.insert(1, 2), &2);
+    /// assert_eq!(MAP.insert(1, 3), &2);
+    /// ```
+    pub const fn with_policy(policy: P) -> Self
+    where
+        P: Copy,
+    {
+        let _ = policy;
+        Self::empty()
     }
     /// Get the number of elements in [PinnedMap].
     pub fn len(&self) -> usize {
@@ -91,63 +234,565 @@ impl<K, V> PinnedMap<K, V> {
         self.sections.read().expect(PANIC).is_empty()
     }
     /// Check if the [PinnedMap] contains a key.
-    pub fn contains_key(&self, key: &K) -> bool
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
     where
-        K: Ord,
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
     {
         self.sections.read().expect(PANIC).contains_key(key)
     }
     /// Push an item into the [PinnedMap]
     /// and return the reference to it.
-    pub fn insert(&self, key: K, value: V) -> &V
+    ///
+    /// If the key is already present,
+    /// the [Policy] decides what to do and what to return.
+    pub fn insert(&self, key: K, value: V) -> P::Output<'_, K, V>
+    where
+        K: Ord,
+        P: Policy,
+    {
+        match self.try_insert(key, value) {
+            Ok(v) => P::inserted(v),
+            Err(duplicate) => P::duplicated(duplicate, |k, v| self.replace(k, v).0),
+        }
+    }
+    /// Push an item into the [PinnedMap] behind an [Arc]
+    /// and return an [ArcRef] to it.
+    ///
+    /// Only available to policies returning a reference from [PinnedMap::insert].
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// use std::sync::Arc;
+    /// let v = Arc::new(PinnedMap::new());
+    /// let a = v.insert_arc(1, 2);
+    /// let b = v.get_arc(&1).unwrap();
+    /// assert!(ArcRef::ptr_eq(&a, &b));
+    /// ```
+    pub fn insert_arc(self: &Arc<Self>, key: K, value: V) -> ArcRef<V>
+    where
+        K: Ord + Send + Sync + 'static,
+        V: Send + Sync + 'static,
+        P: for<'a> Policy<Output<'a, K, V> = &'a V> + 'static,
+    {
+        ArcRef::new(self, |map| map.insert(key, value))
+    }
+    /// Push an item into the [PinnedMap]
+    /// and return the reference to it,
+    /// or hand the item back if the key is already present,
+    /// regardless of the [Policy].
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// let v = PinnedMap::new();
+    /// assert_eq!(v.try_insert(1, 2).ok(), Some(&2));
+    /// let e = v.try_insert(1, 3).unwrap_err();
+    /// assert_eq!((e.key(), e.value(), e.existing()), (&1, &3, &2));
+    /// assert_eq!(e.to_string(), "duplicated key 1");
+    /// ```
+    pub fn try_insert(&self, key: K, value: V) -> Result<&V, DuplicateKey<'_, K, V>>
+    where
+        K: Ord,
+    {
+        let _resume = subscribe::Resume;
+        let mut guard = self.sections.write().expect(PANIC);
+        let r = self.try_insert_locked(&mut guard, key, value);
+        drop(guard);
+        self.notify.notify();
+        r
+    }
+    /// The same as [PinnedMap::try_insert],
+    /// but calls `accept` under the lock before inserting into an absent key,
+    /// and inserts nothing if it fails.
+    #[cfg(feature = "wal")]
+    pub(crate) fn try_insert_if<E>(
+        &self,
+        key: K,
+        value: V,
+        accept: impl FnOnce() -> Result<(), E>,
+    ) -> Result<Result<&V, DuplicateKey<'_, K, V>>, E>
+    where
+        K: Ord,
+    {
+        let _resume = subscribe::Resume;
+        let mut guard = self.sections.write().expect(PANIC);
+        if !guard.contains_key(&key) {
+            accept()?;
+        }
+        let r = self.try_insert_locked(&mut guard, key, value);
+        drop(guard);
+        self.notify.notify();
+        Ok(r)
+    }
+    fn try_insert_locked<'a>(
+        &self,
+        sections: &mut BTreeMap<K, Slot<V>>,
+        key: K,
+        value: V,
+    ) -> Result<&'a V, DuplicateKey<'a, K, V>>
+    where
+        K: Ord,
+    {
+        if let Some(existing) = sections.get(&key) {
+            let r = existing.deref();
+            let r: &V = unsafe { mem::transmute::<&V, &V>(r) };
+            return Err(DuplicateKey::new(key, value, r));
+        }
+        let item = Slot::new(value, &self.next_seq);
+        let r = item.deref();
+        let r: &V = unsafe { mem::transmute::<&V, &V>(r) };
+        subscribe::publish(&self.subscribers, Event::Inserted(&key, r));
+        sections.insert(key, item);
+        Ok(r)
+    }
+    /// Push a lot of items into the [PinnedMap],
+    /// and collect the outputs of [PinnedMap::insert] in input order.
+    ///
+    /// The lock is taken once,
+    /// and only released to apply the [Policy] to a duplicate key.
+    /// So `iter` should not access the [PinnedMap].
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// let v = PinnedMap::new();
+    /// let a: Vec<_> = v.extend_refs([(1, 'a'), (2, 'b'), (1, 'c')]);
+    /// assert_eq!(a, [&'a', &'b', &'c']);
+    /// assert_eq!(v.get(&1), Some(&'c'));
+    /// assert_eq!(v.shadowed_len(), 1);
+    ///
+    /// let v = PinnedMap::with_policy(ReturnError);
+    /// let a: Vec<_> = v.extend_refs([(1, 'a'), (1, 'b')]);
+    /// assert_eq!(a[0].as_ref().ok(), Some(&&'a'));
+    /// assert_eq!(a[1].as_ref().unwrap_err().existing(), &'a');
+    /// ```
+    pub fn extend_refs<'s, I, C>(&'s self, iter: I) -> C
     where
         K: Ord,
+        P: Policy,
+        I: IntoIterator<Item = (K, V)>,
+        C: FromIterator<P::Output<'s, K, V>>,
     {
-        let item = Box::pin(value);
+        let _resume = subscribe::Resume;
+        let mut guard = Some(self.sections.write().expect(PANIC));
+        let outputs = iter
+            .into_iter()
+            .map(|(key, value)| {
+                let sections = guard.get_or_insert_with(|| self.sections.write().expect(PANIC));
+                match self.try_insert_locked(sections, key, value) {
+                    Ok(v) => P::inserted(v),
+                    Err(duplicate) => {
+                        guard = None;
+                        P::duplicated(duplicate, |k, v| self.replace(k, v).0)
+                    }
+                }
+            })
+            .collect();
+        drop(guard);
+        self.notify.notify();
+        outputs
+    }
+    /// Push an item into the [PinnedMap]
+    /// and return the reference to it,
+    /// together with the reference to the item it shadows, if any.
+    ///
+    /// It always shadows, regardless of the [Policy].
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// let v = PinnedMap::new();
+    /// let (a, prev) = v.replace(1, 2);
+    /// assert_eq!((a, prev), (&2, None));
+    /// let (b, prev) = v.replace(1, 3);
+    /// assert_eq!((b, prev), (&3, Some(a)));
+    /// assert_eq!(v.get(&1), Some(&3));
+    /// ```
+    pub fn replace(&self, key: K, value: V) -> (&V, Option<&V>)
+    where
+        K: Ord,
+    {
+        let _resume = subscribe::Resume;
+        let mut guard = self.sections.write().expect(PANIC);
+        let item = Slot::new(value, &self.next_seq);
+        let seq = item.seq;
         let r = item.deref();
         let r: &V = unsafe { mem::transmute::<&V, &V>(r) };
-        let prev = self.sections.write().expect(PANIC).insert(key, item);
-        if let Some(_prev) = prev {
-            #[cfg(feature = "strict")]
-            panic!("internal error: duplicated key");
-            #[cfg(not(feature = "strict"))]
-            self.shadowed.write().expect(PANIC).push(_prev);
+        let prev = guard.remove_entry(&key);
+        let event = match &prev {
+            Some((_, shadowed)) => Event::Shadowed {
+                key: &key,
+                value: r,
+                shadowed,
+            },
+            None => Event::Inserted(&key, r),
+        };
+        subscribe::publish(&self.subscribers, event);
+        guard.insert(key, item);
+        let prev = prev.map(|(k, v)| {
+            let p = v.deref();
+            let p: &V = unsafe { mem::transmute::<&V, &V>(p) };
+            self.shadowed
+                .write()
+                .expect(PANIC)
+                .push((Box::new(k), v, seq));
+            p
+        });
+        drop(guard);
+        self.notify.notify();
+        (r, prev)
+    }
+    /// Get the number of shadowed items.
+    pub fn shadowed_len(&self) -> usize {
+        self.shadowed.read().expect(PANIC).len()
+    }
+    /// Get an iterator over all shadowed items, oldest first.
+    ///
+    /// It iterates over the items shadowed when it is created,
+    /// without holding any lock, so the [PinnedMap] can be accessed meanwhile.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// let v = PinnedMap::new();
+    /// v.insert(1, 'a');
+    /// v.insert(2, 'b');
+    /// v.insert(1, 'c');
+    /// v.insert(2, 'd');
+    /// v.insert(1, 'e');
+    /// assert_eq!(v.shadowed_len(), 3);
+    /// let shadowed: Vec<_> = v.iter_shadowed().collect();
+    /// assert_eq!(shadowed, [(&1, &'a'), (&2, &'b'), (&1, &'c')]);
+    /// ```
+    pub fn iter_shadowed(&self) -> Shadowed<'_, K, V> {
+        Shadowed::new(&self.shadowed.read().expect(PANIC))
+    }
+    /// Get an iterator over items shadowed under `key`, oldest first.
+    ///
+    /// It takes time linear to [PinnedMap::shadowed_len].
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// let v = PinnedMap::new();
+    /// v.insert(1, 'a');
+    /// v.insert(2, 'b');
+    /// v.insert(1, 'c');
+    /// v.insert(1, 'd');
+    /// assert_eq!(v.history(&1).collect::<Vec<_>>(), [&'a', &'c']);
+    /// assert_eq!(v.history(&2).count(), 0);
+    /// ```
+    pub fn history<'a>(&'a self, key: &'a K) -> impl Iterator<Item = &'a V> + 'a
+    where
+        K: Ord,
+    {
+        self.iter_shadowed()
+            .filter_map(move |(k, v)| (k == key).then_some(v))
+    }
+    /// Remember the current state of the [PinnedMap],
+    /// so that it can be [rolled back](PinnedMap::rollback) to.
+    pub fn checkpoint(&self) -> Checkpoint {
+        // Insertions are done under the write lock.
+        let _guard = self.sections.read().expect(PANIC);
+        self.issuer
+            .checkpoint(self.next_seq.load(Ordering::Relaxed))
+    }
+    /// Drop all items inserted after `checkpoint`,
+    /// and bring back the items they shadowed.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// let mut v = PinnedMap::new();
+    /// v.insert(1, 'a');
+    /// let checkpoint = v.checkpoint();
+    /// v.insert(1, 'b');
+    /// v.insert(2, 'c');
+    /// v.rollback(checkpoint).unwrap();
+    /// assert_eq!(v.iter().collect::<Vec<_>>(), [(&1, &'a')]);
+    /// assert_eq!(v.shadowed_len(), 0);
+    /// ```
+    ///
+    /// Nothing is dropped if `checkpoint` was taken on another container,
+    /// or after a point the container has since been rolled back to.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// let v = PinnedMap::<i32, i32>::new();
+    /// let mut w = v.clone();
+    /// assert_eq!(w.rollback(v.checkpoint()), Err(RollbackError::Foreign));
+    /// ```
+    pub fn rollback(&mut self, checkpoint: Checkpoint) -> Result<(), RollbackError>
+    where
+        K: Ord,
+    {
+        let seq = self.issuer.rollback(checkpoint)?;
+        let sections = self.sections.get_mut().expect(PANIC);
+        let shadowed = self.shadowed.get_mut().expect(PANIC);
+        // Newest first, so that the item present at the checkpoint comes back last.
+        while shadowed.last().is_some_and(|(_, _, by)| *by >= seq) {
+            let (key, item, _) = shadowed.pop().expect("internal error: missing item");
+            if item.seq < seq {
+                sections.insert(*key, item);
+            }
         }
-        r
+        sections.retain(|_, item| item.seq < seq);
+        Ok(())
     }
     /// Get an item in [PinnedMap].
-    pub fn get(&self, key: &K) -> Option<&V>
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
     where
-        K: Ord,
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
     {
         self.sections.read().expect(PANIC).get(key).map(|v| {
             let r = v.deref();
             unsafe { mem::transmute::<&V, &V>(r) }
         })
     }
+    /// Get an [ArcRef] to an item in [PinnedMap] behind an [Arc].
+    pub fn get_arc<Q>(self: &Arc<Self>, key: &Q) -> Option<ArcRef<V>>
+    where
+        K: Borrow<Q> + Ord + Send + Sync + 'static,
+        Q: Ord + ?Sized,
+        V: Send + Sync + 'static,
+        P: 'static,
+    {
+        ArcRef::filter_map(ArcRef::new(self, |map| map), |map| map.get(key)).ok()
+    }
+    /// Block until the key is inserted by another thread,
+    /// and return the reference to the item.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// use std::{thread, time::Duration};
+    /// let v = PinnedMap::new();
+    /// thread::scope(|s| {
+    ///     s.spawn(|| assert_eq!(v.wait_get(&1), &2));
+    ///     v.insert(1, 2);
+    /// });
+    /// assert_eq!(v.wait_get_timeout(&2, Duration::from_millis(10)), None);
+    /// ```
+    pub fn wait_get<Q>(&self, key: &Q) -> &V
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        self.notify
+            .wait(|| self.get(key), None)
+            .expect("internal error: waited without timeout")
+    }
+    /// Block until the key is inserted by another thread, or `timeout` elapses.
+    pub fn wait_get_timeout<Q>(&self, key: &Q, timeout: Duration) -> Option<&V>
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        self.notify.wait(|| self.get(key), Some(timeout))
+    }
     /// Get an item in [PinnedMap] if there exists one,
     /// otherwise push an item into the [PinnedMap]
     /// and return the reference to it.
+    ///
+    /// The write lock is only taken if the key is absent.
+    /// If another caller is initializing the same key,
+    /// for example through [PinnedMap::get_or_insert_with],
+    /// this blocks until it finishes, and `value` is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is initializing the same key,
+    /// which would otherwise deadlock.
     pub fn get_or_insert(&self, key: K, value: V) -> &V
     where
         K: Ord,
     {
-        let mut guard = self.sections.write().expect(PANIC);
-        let v = guard.entry(key).or_insert(Box::pin(value));
-        let r = v.deref();
-        unsafe { mem::transmute::<&V, &V>(r) }
+        self.entry(key).or_insert(value)
+    }
+    /// Call [PinnedMap::get_or_insert] on a lot of items,
+    /// and collect the references in input order.
+    ///
+    /// The lock is taken once, so `iter` should not access the [PinnedMap].
+    /// Keys being initialized by other callers are left until the lock is released,
+    /// and then waited for as [PinnedMap::get_or_insert] does.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// let v = PinnedMap::new();
+    /// v.insert(1, 'a');
+    /// let a: Vec<_> = v.get_or_insert_many([(2, 'b'), (1, 'c'), (2, 'd')]);
+    /// assert_eq!(a, [&'b', &'a', &'b']);
+    /// ```
+    pub fn get_or_insert_many<'s, I, C>(&'s self, iter: I) -> C
+    where
+        K: Ord,
+        I: IntoIterator<Item = (K, V)>,
+        C: FromIterator<&'s V>,
+    {
+        let _resume = subscribe::Resume;
+        let mut refs = Vec::new();
+        let mut deferred = Vec::new();
+        {
+            // Locked in the same order as a claim is fulfilled.
+            let pending = self.pending.lock().expect(PANIC);
+            let mut guard = self.sections.write().expect(PANIC);
+            for (key, value) in iter {
+                if pending.iter().any(|(k, _)| **k == key) {
+                    deferred.push((refs.len(), key, value));
+                    refs.push(None);
+                } else {
+                    let r = self.try_insert_locked(&mut guard, key, value);
+                    refs.push(Some(r.unwrap_or_else(|d| d.existing())));
+                }
+            }
+        }
+        self.notify.notify();
+        for (i, key, value) in deferred {
+            refs[i] = Some(self.get_or_insert(key, value));
+        }
+        refs.into_iter()
+            .map(|r| r.expect("internal error: deferred item not inserted"))
+            .collect()
     }
     /// Get an item in [PinnedMap] if there exists one,
     /// otherwise push an item into the [PinnedMap]
     /// and return the reference to it.
+    ///
+    /// `default` runs without holding the lock,
+    /// so it may access the [PinnedMap], including inserting other keys.
+    /// Concurrent callers for the same key block until it finishes
+    /// and get its output, while other keys are not affected.
+    /// If `default` panics, one of the waiting callers runs its own instead.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// let v = PinnedMap::new();
+    /// let a = v.get_or_insert_with(1, || *v.get_or_insert_with(2, || 3) + 1);
+    /// assert_eq!(a, &4);
+    /// assert_eq!(v.get(&2), Some(&3));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `default` asks for the same key again,
+    /// which would otherwise deadlock.
     pub fn get_or_insert_with(&self, key: K, default: impl FnOnce() -> V) -> &V
     where
         K: Ord,
     {
-        let mut guard = self.sections.write().expect(PANIC);
-        let v = guard.entry(key).or_insert_with(|| Box::pin(default()));
-        let r = v.deref();
-        unsafe { mem::transmute::<&V, &V>(r) }
+        match self.get_or_try_insert_with(key, || Ok::<V, Infallible>(default())) {
+            Ok(v) => v,
+            Err(e) => match e {},
+        }
+    }
+    /// Get an item in [PinnedMap] if there exists one,
+    /// otherwise try to push an item into the [PinnedMap]
+    /// and return the reference to it.
+    ///
+    /// Nothing is inserted if `default` fails, and the error is returned as is.
+    /// Callers waiting for the same key then run their own initializers.
+    /// Otherwise the same as [PinnedMap::get_or_insert_with].
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// let v = PinnedMap::new();
+    /// assert_eq!(v.get_or_try_insert_with(1, || "x".parse::<i32>()).ok(), None);
+    /// assert!(v.is_empty());
+    /// assert_eq!(v.get_or_try_insert_with(1, || "2".parse::<i32>()), Ok(&2));
+    /// ```
+    pub fn get_or_try_insert_with<E>(
+        &self,
+        key: K,
+        default: impl FnOnce() -> Result<V, E>,
+    ) -> Result<&V, E>
+    where
+        K: Ord,
+    {
+        match self.entry(key) {
+            Entry::Occupied(entry) => Ok(entry.get()),
+            Entry::Vacant(entry) => Ok(entry.insert(default()?)),
+        }
+    }
+    /// Get the entry of a key for insertion if absent.
+    ///
+    /// If another caller is initializing the same key,
+    /// either through [PinnedMap::get_or_insert_with] or a [VacantEntry],
+    /// this blocks until it finishes.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// let v = PinnedMap::new();
+    /// let a = v.entry(1).or_insert(2);
+    /// let b = v.entry(1).or_insert_with(|| unreachable!());
+    /// assert_eq!(a as *const i32, b as *const i32);
+    /// match v.entry(3) {
+    ///     Entry::Occupied(_) => unreachable!(),
+    ///     Entry::Vacant(entry) => assert_eq!(entry.insert(4), &4),
+    /// };
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is holding a [VacantEntry] of the same key,
+    /// which would otherwise deadlock.
+    pub fn entry(&self, mut key: K) -> Entry<'_, K, V>
+    where
+        K: Ord,
+    {
+        loop {
+            match self.claim(key, true) {
+                Claim::Ready(v, k) => return Entry::occupied(k, v),
+                Claim::Wait(slot, k) => {
+                    key = k;
+                    slot.wait();
+                }
+                Claim::Init(claimed) => return Entry::vacant(claimed),
+            }
+        }
+    }
+    /// Get an item in [PinnedMap] if there exists one,
+    /// otherwise await `default` and push its output into the [PinnedMap].
+    ///
+    /// No lock is held across `.await`.
+    /// If several tasks ask for the same key at the same time,
+    /// only one of them runs its initializer and the others wait for it.
+    /// Should that initializer be cancelled or panic,
+    /// one of the waiting tasks runs its own instead.
+    ///
+    /// It does not rely on any particular executor.
+    ///
+    /// ```rust
+    /// # async fn f() {
+    /// use pinned_bucket::*;
+    /// let v = PinnedMap::new();
+    /// let a = v.get_or_insert_with_async(1, || async { 2 }).await;
+    /// let b = v.get_or_insert_with_async(1, || async { unreachable!() }).await;
+    /// assert_eq!(a, &2);
+    /// assert_eq!(a as *const i32, b as *const i32);
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `default` or its future asks for the same key again,
+    /// which would otherwise deadlock,
+    /// the same as [PinnedMap::get_or_insert_with].
+    /// Tasks spawned by it are not part of it, and simply wait.
+    #[cfg(feature = "async")]
+    pub async fn get_or_insert_with_async<F, Fut>(&self, mut key: K, default: F) -> &V
+    where
+        K: Ord,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = V>,
+    {
+        loop {
+            match self.claim(key, false) {
+                Claim::Ready(v, _) => return v,
+                Claim::Wait(slot, k) => {
+                    key = k;
+                    poll_fn(|cx| slot.poll_done(cx)).await;
+                }
+                Claim::Init(claimed) => {
+                    let value = claimed.run(default).await;
+                    return claimed.fulfill(value);
+                }
+            }
+        }
     }
     /// Get all keys.
     pub fn keys(&self) -> Keys<'_, K, V>
@@ -173,7 +818,7 @@ impl<K, V> PinnedMap<K, V> {
         IntoIterator::into_iter(self)
     }
 }
-impl<'a, K, V> IntoIterator for &'a PinnedMap<K, V> {
+impl<'a, K, V, P> IntoIterator for &'a PinnedMap<K, V, P> {
     type Item = (&'a K, &'a V);
     type IntoIter = Iter<'a, K, V>;
     fn into_iter(self) -> Self::IntoIter {
@@ -181,18 +826,26 @@ impl<'a, K, V> IntoIterator for &'a PinnedMap<K, V> {
         Iter::new(guard)
     }
 }
-impl<K: Clone, V: Clone> Clone for PinnedMap<K, V> {
+impl<K: Debug, V: Debug, P> Debug for PinnedMap<K, V, P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PinnedMap")
+            .field("sections", &self.sections)
+            .finish()
+    }
+}
+impl<K: Clone, V: Clone, P> Clone for PinnedMap<K, V, P> {
     fn clone(&self) -> Self {
         let values = self.sections.read().expect(PANIC);
         let sections = values.clone().into();
-        #[cfg(feature = "strict")]
-        {
-            Self { sections }
-        }
-        #[cfg(not(feature = "strict"))]
-        {
-            let shadowed = RwLock::new(Vec::new());
-            Self { sections, shadowed }
+        Self {
+            sections,
+            shadowed: RwLock::new(Vec::new()),
+            pending: Mutex::new(Vec::new()),
+            next_seq: AtomicU64::new(self.next_seq.load(Ordering::Relaxed)),
+            issuer: Issuer::new(),
+            notify: Notify::new(),
+            subscribers: Mutex::new(subscribe::Subscribers::new()),
+            policy: PhantomData,
         }
     }
 }
@@ -256,6 +909,19 @@ mod tests {
         assert_eq!(v.values().cloned().collect::<Vec<_>>(), vec![2, 4]);
     }
 
+    #[test]
+    fn debug_items_only() {
+        let v = PinnedMap::new();
+        v.insert(1, 2);
+        v.checkpoint();
+        let _sub = v.subscribe(|_| ());
+        assert_eq!(format!("{:?}", v), format!("{:?}", v.clone()));
+        assert_eq!(
+            format!("{:?}", v),
+            "PinnedMap { sections: RwLock { data: {1: 2}, poisoned: false, .. } }"
+        );
+    }
+
     #[test]
     fn debug_keys() {
         let v: PinnedMap<usize, String> = PinnedMap::default();
@@ -270,7 +936,6 @@ mod tests {
     }
 
     #[test]
-    #[cfg_attr(feature = "strict", should_panic = "internal error: duplicated key")]
     fn insert_duplicate() {
         let v = PinnedMap::new();
         let a = v.insert(1, "1".to_owned());
@@ -283,6 +948,219 @@ mod tests {
         assert!(v.contains_key(&1));
     }
 
+    #[test]
+    #[should_panic = "duplicated key"]
+    fn insert_duplicate_panic() {
+        let v = PinnedMap::with_policy(crate::Panic);
+        v.insert(1, "1".to_owned());
+        v.insert(1, "2".to_owned());
+    }
+
+    #[test]
+    fn insert_duplicate_policies() {
+        let v = PinnedMap::with_policy(crate::KeepFirst);
+        let a = v.insert(1, "1".to_owned());
+        let b = v.insert(1, "2".to_owned());
+        assert_eq!(a as *const String, b as *const String);
+        assert_eq!(v.shadowed_len(), 0);
+
+        let v = PinnedMap::with_policy(crate::ReturnError);
+        let a = v.insert(1, "1".to_owned()).unwrap();
+        let e = v.insert(1, "2".to_owned()).unwrap_err();
+        assert_eq!(e.existing() as *const String, a as *const String);
+        assert_eq!(e.into_inner(), (1, "2".to_owned()));
+        assert_eq!(v.get(&1), Some(a));
+        // Explicit replacement always shadows.
+        let (c, prev) = v.replace(1, "3".to_owned());
+        assert_eq!(c, "3");
+        assert_eq!(prev, Some(a));
+        assert_eq!(v.shadowed_len(), 1);
+    }
+
+    #[test]
+    fn extend_refs() {
+        let v = PinnedMap::with_policy(crate::KeepFirst);
+        let a: Vec<_> = v.extend_refs((0..4).map(|i| (i % 2, i)));
+        assert_eq!(a, [&0, &1, &0, &1]);
+        assert_eq!(v.len(), 2);
+        assert_eq!(v.shadowed_len(), 0);
+
+        let v = PinnedMap::with_policy(crate::Panic);
+        let r = std::panic::catch_unwind(|| v.extend_refs::<_, Vec<_>>([(1, 1), (1, 2)]));
+        assert!(r.is_err());
+        // The lock is not poisoned.
+        assert_eq!(v.get(&1), Some(&1));
+
+        let v = PinnedMap::new();
+        v.insert(1, 1);
+        let a: Vec<_> = v.get_or_insert_many([(1, 2), (2, 3)]);
+        assert_eq!(a, [&1, &3]);
+        assert_eq!(v.shadowed_len(), 0);
+    }
+
+    #[test]
+    fn rollback() {
+        let mut v = PinnedMap::new();
+        v.insert(1, 'a');
+        v.insert(1, 'b');
+        let first = v.checkpoint();
+        v.insert(1, 'c');
+        v.insert(2, 'd');
+        let second = v.checkpoint();
+        v.insert(1, 'e');
+        v.insert(2, 'f');
+        v.insert(3, 'g');
+        v.rollback(second).unwrap();
+        assert_eq!(v.iter().collect::<Vec<_>>(), [(&1, &'c'), (&2, &'d')]);
+        assert_eq!(v.shadowed_len(), 2);
+        v.rollback(first).unwrap();
+        assert_eq!(v.iter().collect::<Vec<_>>(), [(&1, &'b')]);
+        assert_eq!(v.iter_shadowed().collect::<Vec<_>>(), [(&1, &'a')]);
+        // Checkpoints can be reused.
+        v.insert(2, 'h');
+        v.rollback(first).unwrap();
+        assert_eq!(v.len(), 1);
+        // Items inserted through entries are rolled back as well.
+        let third = v.checkpoint();
+        v.entry(4).or_insert('i');
+        v.get_or_insert(5, 'j');
+        v.rollback(third).unwrap();
+        assert_eq!(v.len(), 1);
+    }
+
+    #[test]
+    fn rollback_stale() {
+        let mut v = PinnedMap::new();
+        let first = v.checkpoint();
+        v.insert(1, 'a');
+        let second = v.checkpoint();
+        v.rollback(first).unwrap();
+        v.insert(2, 'b');
+        v.insert(3, 'c');
+        assert_eq!(v.rollback(second), Err(RollbackError::Discarded));
+        assert_eq!(v.len(), 2);
+    }
+
+    #[test]
+    fn subscribe() {
+        use std::sync::mpsc;
+        let v = PinnedMap::new();
+        let (tx, rx) = mpsc::channel();
+        let sub = v.subscribe(move |e| {
+            let e = match e {
+                Event::Inserted(k, v) => (*k, *v, None),
+                Event::Shadowed {
+                    key,
+                    value,
+                    shadowed,
+                } => (*key, *value, Some(*shadowed)),
+            };
+            tx.send(e).unwrap();
+        });
+        let _: Vec<_> = v.extend_refs([(1, 'a'), (2, 'b'), (1, 'c')]);
+        v.get_or_insert(2, 'd');
+        v.get_or_insert_with(3, || 'e');
+        v.entry(4).or_insert('f');
+        let events: Vec<_> = rx.try_iter().collect();
+        assert_eq!(
+            events,
+            [
+                (1, 'a', None),
+                (2, 'b', None),
+                (1, 'c', Some('a')),
+                (3, 'e', None),
+                (4, 'f', None),
+            ]
+        );
+        drop(sub);
+        v.insert(5, 'g');
+        assert_eq!(rx.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn subscribe_panic() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let v = PinnedMap::new();
+        let calls = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let _a = v.subscribe(|_| panic!("callback"));
+        let _b = v.subscribe(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let r = catch_unwind(AssertUnwindSafe(|| v.insert(1, 'a')));
+        assert!(r.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        // The item is inserted, and the map is not poisoned.
+        assert_eq!(v.get(&1), Some(&'a'));
+        let r = catch_unwind(AssertUnwindSafe(|| v.get_or_insert_with(2, || 'b')));
+        assert!(r.is_err());
+        assert_eq!(v.len(), 2);
+    }
+
+    #[test]
+    fn unsubscribe_while_iterating() {
+        let v = PinnedMap::new();
+        v.insert(1, 'a');
+        let sub = v.subscribe(|_| ());
+        for _ in &v {
+            let _other = v.subscribe(|_| ());
+        }
+        let iter = v.iter();
+        drop(sub);
+        drop(iter);
+        v.insert(2, 'b');
+    }
+
+    #[test]
+    fn shadowed() {
+        let v = PinnedMap::new();
+        let a = v.insert(1, "1".to_owned());
+        let b = v.insert(2, "2".to_owned());
+        let (c, prev) = v.replace(1, "3".to_owned());
+        assert_eq!(prev.map(|p| p as *const String), Some(a as *const String));
+        let (d, prev) = v.replace(1, "4".to_owned());
+        assert_eq!(prev.map(|p| p as *const String), Some(c as *const String));
+        assert_eq!(v.get(&1), Some(d));
+        assert_eq!(v.get(&2), Some(b));
+        assert_eq!(v.len(), 2);
+        assert_eq!(v.shadowed_len(), 2);
+        assert_eq!(v.iter_shadowed().len(), 2);
+        assert_eq!(
+            format!("{:?}", v.iter_shadowed()),
+            r#"[(1, "1"), (1, "3")]"#
+        );
+        let history: Vec<_> = v.history(&1).map(|s| s as *const String).collect();
+        assert_eq!(history, [a as *const String, c as *const String]);
+        assert_eq!(v.history(&2).count(), 0);
+        // Clones don't keep the history.
+        assert_eq!(v.clone().shadowed_len(), 0);
+    }
+
+    #[test]
+    fn get_while_iter_shadowed() {
+        let v = PinnedMap::new();
+        for i in 0..8 {
+            v.insert(i % 4, i);
+        }
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 8..2000 {
+                    v.insert(i % 4, i);
+                }
+            });
+            s.spawn(|| {
+                for _ in 0..200 {
+                    for (k, _) in v.iter_shadowed() {
+                        assert!(v.get(k).is_some());
+                    }
+                }
+            });
+        });
+        assert_eq!(v.shadowed_len(), 1996);
+    }
+
     #[test]
     fn insert_with() {
         let v = PinnedMap::new();
@@ -306,6 +1184,153 @@ mod tests {
         v.get_or_insert_with(3, unreachable);
     }
 
+    #[test]
+    fn try_insert_with() {
+        let v = PinnedMap::new();
+        assert_eq!(v.get_or_try_insert_with(1, || Err(())), Err(()));
+        assert!(v.is_empty());
+        assert!(!v.contains_key(&1));
+        let a = v.get_or_try_insert_with(1, || Ok::<_, ()>(2));
+        assert_eq!(a, Ok(&2));
+        let b = v.get_or_try_insert_with(1, || Err(()));
+        assert_eq!(b, Ok(&2));
+        assert_eq!(v.len(), 1);
+    }
+
+    #[test]
+    fn entry() {
+        let v = PinnedMap::new();
+        let a = v.entry(1).or_insert(2);
+        assert_eq!(v.entry(1).or_insert(3), &2);
+        assert_eq!(v.entry(2).or_insert_with_key(|k| k + 1), &3);
+        assert_eq!(v.entry(3).or_default(), &0);
+        let mut seen = None;
+        let e = v.entry(1).and_inspect(|v| seen = Some(*v));
+        assert_eq!(e.key(), &1);
+        assert!(matches!(e, Entry::Occupied(_)));
+        assert_eq!(seen, Some(2));
+        assert_eq!(a as *const i32, v.get(&1).unwrap() as *const i32);
+
+        let Entry::Vacant(vacant) = v.entry(4).and_inspect(|_| unreachable!()) else {
+            unreachable!()
+        };
+        assert_eq!(vacant.key(), &4);
+        assert_eq!(format!("{:?}", vacant), "VacantEntry(4)");
+        // Other keys can be accessed while holding a vacant entry.
+        assert_eq!(v.get_or_insert_with(5, || 6), &6);
+        assert_eq!(vacant.into_key(), 4);
+        assert!(!v.contains_key(&4));
+        assert_eq!(v.entry(4).or_insert(7), &7);
+        assert_eq!(v.len(), 5);
+    }
+
+    #[test]
+    fn entry_insert_race() {
+        let v = PinnedMap::new();
+        let Entry::Vacant(vacant) = v.entry(1) else {
+            unreachable!()
+        };
+        let a = v.insert(1, 2);
+        assert_eq!(vacant.insert(3), a);
+    }
+
+    #[test]
+    fn get_or_insert_waits_for_entry() {
+        use std::{thread, time::Duration};
+
+        let v = PinnedMap::new();
+        let Entry::Vacant(vacant) = v.entry(1) else {
+            unreachable!()
+        };
+        thread::scope(|s| {
+            let other = s.spawn(|| v.get_or_insert(1, 3) as *const i32 as usize);
+            thread::sleep(Duration::from_millis(20));
+            let a = vacant.insert(2);
+            assert_eq!(other.join().unwrap(), a as *const i32 as usize);
+        });
+        assert_eq!(v.get(&1), Some(&2));
+    }
+
+    #[test]
+    fn get_or_insert_many_waits_for_entry() {
+        use std::{thread, time::Duration};
+
+        let v = PinnedMap::new();
+        let Entry::Vacant(vacant) = v.entry(1) else {
+            unreachable!()
+        };
+        thread::scope(|s| {
+            let other = s.spawn(|| {
+                let refs: Vec<_> = v.get_or_insert_many([(0, 0), (1, 3), (2, 4)]);
+                refs.into_iter().copied().collect::<Vec<_>>()
+            });
+            thread::sleep(Duration::from_millis(20));
+            vacant.insert(2);
+            assert_eq!(other.join().unwrap(), [0, 2, 4]);
+        });
+        assert_eq!(v.len(), 3);
+    }
+
+    #[test]
+    fn insert_with_reentrant() {
+        let v = PinnedMap::new();
+        let a = v.get_or_insert_with(1, || {
+            let b = v.get_or_insert_with(2, || v.len() + 10);
+            assert!(v.contains_key(&2));
+            *b + 1
+        });
+        assert_eq!(a, &11);
+        assert_eq!(v.get(&2), Some(&10));
+        assert_eq!(v.len(), 2);
+    }
+
+    #[test]
+    #[should_panic = "reentrant initialization of the same key"]
+    fn insert_with_reentrant_same_key() {
+        let v = PinnedMap::new();
+        v.get_or_insert_with(1, || *v.get_or_insert_with(1, || 2));
+    }
+
+    #[test]
+    fn insert_with_concurrent() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::{thread, time::Duration};
+
+        let v = PinnedMap::new();
+        let calls = AtomicUsize::new(0);
+        let refs: Vec<_> = thread::scope(|s| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| {
+                    s.spawn(|| {
+                        let r = v.get_or_insert_with(1, || {
+                            calls.fetch_add(1, Ordering::SeqCst);
+                            thread::sleep(Duration::from_millis(20));
+                            2
+                        });
+                        r as *const i32 as usize
+                    })
+                })
+                .collect();
+            // Other keys are not blocked by the slow initializer.
+            v.get_or_insert_with(3, || 4);
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(refs.iter().all(|r| *r == refs[0]));
+        assert_eq!(v.len(), 2);
+    }
+
+    #[test]
+    fn insert_with_retry_after_panic() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let v = PinnedMap::new();
+        let res = catch_unwind(AssertUnwindSafe(|| v.get_or_insert_with(1, unreachable)));
+        assert!(res.is_err());
+        assert!(v.is_empty());
+        assert_eq!(v.get_or_insert_with(1, || 2), &2);
+    }
+
     #[test]
     fn push_while_iter() {
         let m = PinnedMap::new();
@@ -0,0 +1,152 @@
+use std::fmt::Debug;
+
+use super::pending::Claimed;
+
+/// A view into a single entry in a [super::PinnedMap],
+/// which may either be vacant or occupied.
+///
+/// Constructed by [super::PinnedMap::entry].
+///
+/// Unlike [std::collections::btree_map::Entry],
+/// it does not lock the map,
+/// and the references it returns live as long as the map.
+pub enum Entry<'a, K: Ord, V> {
+    /// An occupied entry.
+    Occupied(OccupiedEntry<'a, K, V>),
+    /// A vacant entry.
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+/// A view into an occupied entry in a [super::PinnedMap].
+/// It is part of the [Entry] enum.
+pub struct OccupiedEntry<'a, K, V> {
+    key: K,
+    value: &'a V,
+}
+
+/// A view into a vacant entry in a [super::PinnedMap].
+/// It is part of the [Entry] enum.
+///
+/// While it is alive, other callers asking for the same key
+/// through [super::PinnedMap::entry] or [super::PinnedMap::get_or_insert_with]
+/// wait for it to be filled or dropped.
+pub struct VacantEntry<'a, K: Ord, V> {
+    pub(super) claimed: Claimed<'a, K, V>,
+}
+
+impl<'a, K: Ord, V> Entry<'a, K, V> {
+    pub(super) fn occupied(key: K, value: &'a V) -> Self {
+        Self::Occupied(OccupiedEntry { key, value })
+    }
+    pub(super) fn vacant(claimed: Claimed<'a, K, V>) -> Self {
+        Self::Vacant(VacantEntry { claimed })
+    }
+    /// Ensure a value is in the entry by inserting `default` if empty,
+    /// and return the reference to the value.
+    pub fn or_insert(self, default: V) -> &'a V {
+        match self {
+            Self::Occupied(entry) => entry.get(),
+            Self::Vacant(entry) => entry.insert(default),
+        }
+    }
+    /// Ensure a value is in the entry by inserting the result of `default` if empty,
+    /// and return the reference to the value.
+    pub fn or_insert_with(self, default: impl FnOnce() -> V) -> &'a V {
+        match self {
+            Self::Occupied(entry) => entry.get(),
+            Self::Vacant(entry) => entry.insert(default()),
+        }
+    }
+    /// Ensure a value is in the entry by inserting the result of `default` if empty,
+    /// and return the reference to the value.
+    ///
+    /// `default` is given a reference to the key.
+    pub fn or_insert_with_key(self, default: impl FnOnce(&K) -> V) -> &'a V {
+        match self {
+            Self::Occupied(entry) => entry.get(),
+            Self::Vacant(entry) => {
+                let value = default(entry.key());
+                entry.insert(value)
+            }
+        }
+    }
+    /// Ensure a value is in the entry by inserting the default value if empty,
+    /// and return the reference to the value.
+    pub fn or_default(self) -> &'a V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+    /// Call `f` with the value if the entry is occupied.
+    pub fn and_inspect(self, f: impl FnOnce(&V)) -> Self {
+        if let Self::Occupied(entry) = &self {
+            f(entry.get());
+        }
+        self
+    }
+    /// Get the key of this entry.
+    pub fn key(&self) -> &K {
+        match self {
+            Self::Occupied(entry) => entry.key(),
+            Self::Vacant(entry) => entry.key(),
+        }
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    /// Get the key of this entry.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+    /// Get the value of this entry.
+    pub fn get(&self) -> &'a V {
+        self.value
+    }
+    /// Take the key back.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+}
+
+impl<'a, K: Ord, V> VacantEntry<'a, K, V> {
+    /// Get the key of this entry.
+    pub fn key(&self) -> &K {
+        self.claimed.key()
+    }
+    /// Give up the entry and take the key back.
+    pub fn into_key(self) -> K {
+        self.claimed.into_key()
+    }
+    /// Insert the value and return the reference to it.
+    ///
+    /// If the key has been inserted by [super::PinnedMap::insert] in the meantime,
+    /// `value` is dropped and the existing value is returned instead.
+    pub fn insert(self, value: V) -> &'a V {
+        self.claimed.fulfill(value)
+    }
+}
+
+impl<K: Ord + Debug, V: Debug> Debug for Entry<'_, K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Occupied(entry) => f.debug_tuple("Entry").field(entry).finish(),
+            Self::Vacant(entry) => f.debug_tuple("Entry").field(entry).finish(),
+        }
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for OccupiedEntry<'_, K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OccupiedEntry")
+            .field("key", &self.key)
+            .field("value", self.value)
+            .finish()
+    }
+}
+
+impl<K: Ord + Debug, V> Debug for VacantEntry<'_, K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("VacantEntry").field(self.key()).finish()
+    }
+}
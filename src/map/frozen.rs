@@ -0,0 +1,124 @@
+use super::{PinnedMap, Slot};
+use crate::PANIC;
+use core::{borrow::Borrow, fmt::Debug};
+use std::{collections::BTreeMap, sync::RwLockReadGuard};
+
+/// A read-only view of a [PinnedMap] without a lock on each access.
+///
+/// Constructed by [PinnedMap::freeze].
+/// It keeps the [PinnedMap] read-locked until dropped,
+/// so inserting into it meanwhile blocks,
+/// and deadlocks if done on the same thread.
+pub struct FrozenPinnedMap<'a, K, V> {
+    guard: RwLockReadGuard<'a, BTreeMap<K, Slot<V>>>,
+}
+
+impl<K, V, P> PinnedMap<K, V, P> {
+    /// Give up inserting for a while, and read without taking the lock every time.
+    ///
+    /// It only borrows the [PinnedMap],
+    /// so references handed out before stay valid.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// let v = PinnedMap::new();
+    /// let a = v.insert(1, 2);
+    /// v.insert(3, 4);
+    /// let frozen = v.freeze();
+    /// assert_eq!(frozen.get(&1).unwrap() as *const i32, a as *const i32);
+    /// assert_eq!(frozen.iter().collect::<Vec<_>>(), [(&1, &2), (&3, &4)]);
+    /// drop(frozen);
+    /// v.insert(5, 6);
+    /// assert_eq!(a, &2);
+    /// ```
+    pub fn freeze(&self) -> FrozenPinnedMap<'_, K, V> {
+        FrozenPinnedMap {
+            guard: self.sections.read().expect(PANIC),
+        }
+    }
+}
+
+impl<K, V> FrozenPinnedMap<'_, K, V> {
+    /// Get the number of elements in [FrozenPinnedMap].
+    pub fn len(&self) -> usize {
+        self.guard.len()
+    }
+    /// Check if the [FrozenPinnedMap] is empty.
+    pub fn is_empty(&self) -> bool {
+        self.guard.is_empty()
+    }
+    /// Check if the [FrozenPinnedMap] contains a key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        self.guard.contains_key(key)
+    }
+    /// Get an item in [FrozenPinnedMap].
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        self.guard.get(key).map(|v| &**v)
+    }
+    /// Get an item in [FrozenPinnedMap] together with its key.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        self.guard.get_key_value(key).map(|(k, v)| (k, &**v))
+    }
+    /// Get all keys.
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> + ExactSizeIterator {
+        self.guard.keys()
+    }
+    /// Get all values.
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> + ExactSizeIterator {
+        self.guard.values().map(|v| &**v)
+    }
+    /// Get an iterator over all items.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&K, &V)> + ExactSizeIterator {
+        self.guard.iter().map(|(k, v)| (k, &**v))
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for FrozenPinnedMap<'_, K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn frozen() {
+        let map = PinnedMap::new();
+        let a = map.insert(1, 'a');
+        map.insert(1, 'b');
+        map.insert(2, 'c');
+        let frozen = map.freeze();
+        assert_eq!(frozen.len(), 2);
+        assert!(frozen.contains_key(&2));
+        assert_eq!(frozen.get_key_value(&1), Some((&1, &'b')));
+        assert_eq!(frozen.values().rev().collect::<Vec<_>>(), [&'c', &'b']);
+        assert_eq!(format!("{:?}", frozen), "{1: 'b', 2: 'c'}");
+        thread::scope(|s| {
+            for k in 1..3 {
+                let frozen = &frozen;
+                s.spawn(move || assert!(frozen.get(&k).is_some()));
+            }
+        });
+        // References handed out before the freeze are still usable,
+        // including shadowed ones.
+        assert_eq!(a, &'a');
+        drop(frozen);
+        map.insert(3, 'd');
+        assert_eq!(a, &'a');
+    }
+}
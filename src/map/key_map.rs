@@ -0,0 +1,270 @@
+use super::{Entry, PinnedMap};
+use crate::{policy::DuplicateKey, PANIC};
+use alloc::boxed::Box;
+use core::{borrow::Borrow, mem, ops::Deref};
+use std::fmt::Debug;
+
+/// A key boxed on the heap, so that it never moves.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct PinnedKey<K>(Box<K>);
+
+/// A borrowed form of [PinnedKey],
+/// so that it can be looked up by anything `K` borrows as.
+#[repr(transparent)]
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct Query<Q: ?Sized>(Q);
+
+impl<Q: ?Sized> Query<Q> {
+    fn new(q: &Q) -> &Self {
+        // `Query<Q>` is a transparent wrapper of `Q`.
+        unsafe { &*(q as *const Q as *const Self) }
+    }
+}
+
+impl<K: Borrow<Q>, Q: ?Sized> Borrow<Query<Q>> for PinnedKey<K> {
+    fn borrow(&self) -> &Query<Q> {
+        Query::new((*self.0).borrow())
+    }
+}
+
+impl<K: Debug> Debug for PinnedKey<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&*self.0, f)
+    }
+}
+
+impl<K> PinnedKey<K> {
+    /// Get the reference to the key, which lives as long as `self` is not dropped,
+    /// wherever `self` is moved to.
+    fn erase<'a>(&self) -> &'a K {
+        let r: &K = &self.0;
+        unsafe { mem::transmute::<&K, &K>(r) }
+    }
+    fn into_inner(self) -> K {
+        *self.0
+    }
+}
+
+/// A [PinnedMap] whose keys are pinned as well.
+///
+/// Keys can't move inside the container,
+/// so references to them live as long as the container,
+/// the same as those to values.
+/// This makes it suitable for interning keys.
+///
+/// ```rust
+/// use pinned_bucket::*;
+/// let v = PinnedKeyMap::new();
+/// let (a, _) = v.get_or_insert("a".to_owned(), 1);
+/// let (b, _) = v.get_or_insert("a".to_owned(), 2);
+/// assert_eq!(a as *const String, b as *const String);
+/// assert_eq!(v.get_key_value("a"), Some((a, &1)));
+/// ```
+///
+/// As the items inside are still managed by the container,
+/// codes below won't compile.
+///
+/// ```compile_fail
+/// use pinned_bucket::*;
+/// let v = PinnedKeyMap::new();
+/// let (a, _) = v.insert(1, 2);
+/// drop(v);
+/// assert_eq!(a, &1);
+/// ```
+///
+/// Each key takes an extra allocation,
+/// so prefer [PinnedMap] if references to keys are not needed.
+/// Inserting a key already present shadows the previous item,
+/// as [PinnedMap::replace] does.
+pub struct PinnedKeyMap<K, V> {
+    inner: PinnedMap<PinnedKey<K>, V>,
+}
+impl<K, V> Default for PinnedKeyMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<K, V> PinnedKeyMap<K, V> {
+    /// Create an empty [PinnedKeyMap].
+    pub const fn new() -> Self {
+        Self {
+            inner: PinnedMap::new(),
+        }
+    }
+    /// Get the number of elements in [PinnedKeyMap].
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+    /// Check if the [PinnedKeyMap] is empty.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+    /// Check if the [PinnedKeyMap] contains a key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+    /// Push an item into the [PinnedKeyMap]
+    /// and return the references to its key and value.
+    pub fn insert(&self, key: K, value: V) -> (&K, &V)
+    where
+        K: Ord,
+    {
+        let key = PinnedKey(Box::new(key));
+        let k = key.erase();
+        let (v, _) = self.inner.replace(key, value);
+        (k, v)
+    }
+    /// Push an item into the [PinnedKeyMap]
+    /// and return the references to its key and value,
+    /// or hand the item back if the key is already present.
+    pub fn try_insert(&self, key: K, value: V) -> Result<(&K, &V), DuplicateKey<'_, K, V>>
+    where
+        K: Ord,
+    {
+        let key = PinnedKey(Box::new(key));
+        let k = key.erase();
+        match self.inner.try_insert(key, value) {
+            Ok(v) => Ok((k, v)),
+            Err(e) => {
+                let existing = e.existing();
+                let (key, value) = e.into_inner();
+                Err(DuplicateKey::new(key.into_inner(), value, existing))
+            }
+        }
+    }
+    /// Get an item in [PinnedKeyMap] if there exists one,
+    /// otherwise push an item into the [PinnedKeyMap],
+    /// and return the references to its key and value.
+    pub fn get_or_insert(&self, key: K, value: V) -> (&K, &V)
+    where
+        K: Ord,
+    {
+        self.get_or_insert_with(key, || value)
+    }
+    /// Get an item in [PinnedKeyMap] if there exists one,
+    /// otherwise push an item into the [PinnedKeyMap],
+    /// and return the references to its key and value.
+    ///
+    /// See [PinnedMap::get_or_insert_with] for how `default` runs.
+    pub fn get_or_insert_with(&self, key: K, default: impl FnOnce() -> V) -> (&K, &V)
+    where
+        K: Ord,
+    {
+        const PRESENT: &str = "internal error: keys are not removed while the map is shared";
+        match self.inner.entry(PinnedKey(Box::new(key))) {
+            Entry::Occupied(entry) => self.get_key_value(&*entry.key().0).expect(PRESENT),
+            Entry::Vacant(entry) => {
+                let k = entry.key().erase();
+                match entry.claimed.fulfill_full(default()) {
+                    (v, None) => (k, v),
+                    // Inserted by someone else in the meantime.
+                    (_, Some(key)) => self.get_key_value(&*key.0).expect(PRESENT),
+                }
+            }
+        }
+    }
+    /// Get an item in [PinnedKeyMap].
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        self.get_key_value(key).map(|(_, v)| v)
+    }
+    /// Get an item in [PinnedKeyMap] with its key.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        let guard = self.inner.sections.read().expect(PANIC);
+        let (k, v) = guard.get_key_value(Query::new(key))?;
+        let v = v.deref();
+        Some((k.erase(), unsafe { mem::transmute::<&V, &V>(v) }))
+    }
+    /// Get an iterator over all items, ordered by key.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&K, &V)> + ExactSizeIterator
+    where
+        K: Ord,
+    {
+        self.inner.iter().map(|(k, v)| (k.erase(), v))
+    }
+    /// Get all keys, ordered.
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> + ExactSizeIterator
+    where
+        K: Ord,
+    {
+        self.inner.keys().map(PinnedKey::erase)
+    }
+}
+impl<K: Ord + Debug, V: Debug> Debug for PinnedKeyMap<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+impl<K: Clone, V: Clone> Clone for PinnedKeyMap<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stable_keys() {
+        let v = PinnedKeyMap::new();
+        let (k, a) = v.insert("0".to_owned(), 0);
+        for i in 1..64 {
+            v.insert(i.to_string(), i);
+        }
+        assert_eq!(v.len(), 64);
+        let (k_, a_) = v.get_key_value("0").unwrap();
+        assert_eq!(k as *const String, k_ as *const String);
+        assert_eq!(a as *const i32, a_ as *const i32);
+        assert_eq!(v.keys().next(), Some(k));
+        assert!(v.contains_key("63"));
+        assert_eq!(v.get("64"), None);
+    }
+
+    #[test]
+    fn insert_duplicate() {
+        let v = PinnedKeyMap::new();
+        let (k, a) = v.insert(1, "a".to_owned());
+        let (k_, b) = v.insert(1, "b".to_owned());
+        assert_ne!(k as *const i32, k_ as *const i32);
+        assert_eq!(v.get_key_value(&1), Some((k_, b)));
+        assert_eq!(a, "a");
+
+        let e = v.try_insert(1, "c".to_owned()).unwrap_err();
+        assert_eq!(e.existing(), b);
+        assert_eq!(e.into_inner(), (1, "c".to_owned()));
+        assert_eq!(v.try_insert(2, "d".to_owned()).unwrap().1, "d");
+        assert_eq!(format!("{:?}", v), r#"{1: "b", 2: "d"}"#);
+    }
+
+    #[test]
+    fn get_or_insert() {
+        let v = PinnedKeyMap::new();
+        let (k, a) = v.get_or_insert_with("a".to_owned(), || {
+            // Inserted while initializing, which takes precedence.
+            v.insert("a".to_owned(), 2);
+            3
+        });
+        assert_eq!(a, &2);
+        let (k_, a_) = v.get_key_value("a").unwrap();
+        assert_eq!(k as *const String, k_ as *const String);
+        assert_eq!(a as *const i32, a_ as *const i32);
+        let (k2, b) = v.get_or_insert("a".to_owned(), 4);
+        assert_eq!(k as *const String, k2 as *const String);
+        assert_eq!(b, &2);
+        assert_eq!(v.iter().len(), 1);
+    }
+}
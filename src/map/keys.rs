@@ -2,21 +2,26 @@ use std::{
     collections::{btree_map as map, BTreeMap},
     fmt::Debug,
     iter::FusedIterator,
-    pin::Pin,
     sync::RwLockReadGuard,
 };
 
+use super::Slot;
+
 /// Iterator over keys of [super::PinnedMap].
 pub struct Keys<'a, K, V> {
     /// Shall not be read. Only kept here to prevent the map from being modified.
     #[allow(unused)]
-    guard: RwLockReadGuard<'a, BTreeMap<K, Pin<Box<V>>>>,
-    inner: map::Keys<'a, K, Pin<Box<V>>>,
+    guard: RwLockReadGuard<'a, BTreeMap<K, Slot<V>>>,
+    inner: map::Keys<'a, K, Slot<V>>,
 }
 
 impl<'a, K, V> Keys<'a, K, V> {
-    pub(super) fn new(guard: RwLockReadGuard<'a, BTreeMap<K, Pin<Box<V>>>>) -> Self {
-        let inner = unsafe { std::mem::transmute(guard.keys()) };
+    pub(super) fn new(guard: RwLockReadGuard<'a, BTreeMap<K, Slot<V>>>) -> Self {
+        let inner = unsafe {
+            std::mem::transmute::<map::Keys<'_, K, Slot<V>>, map::Keys<'a, K, Slot<V>>>(
+                guard.keys(),
+            )
+        };
         Self { guard, inner }
     }
 }
@@ -0,0 +1,61 @@
+use super::PinnedMap;
+use crate::policy::Policy;
+use alloc::vec::Vec;
+use rayon::{
+    iter::{FromParallelIterator, IntoParallelIterator, ParallelExtend, ParallelIterator},
+    vec::IntoIter,
+};
+
+impl<K: Ord + Sync, V: Sync, P> PinnedMap<K, V, P> {
+    /// Get a parallel iterator over all items.
+    ///
+    /// It iterates over the items present when called,
+    /// and doesn't hold the lock afterwards.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// use rayon::prelude::*;
+    /// let v: PinnedMap<_, _> = (0..4).into_par_iter().map(|i| (i, i * i)).collect();
+    /// assert_eq!(v.par_iter().map(|(k, v)| k + v).sum::<i32>(), 20);
+    /// ```
+    pub fn par_iter(&self) -> IntoIter<(&K, &V)> {
+        self.iter().collect::<Vec<_>>().into_par_iter()
+    }
+}
+
+impl<'a, K: Ord + Sync, V: Sync, P> IntoParallelIterator for &'a PinnedMap<K, V, P> {
+    type Iter = IntoIter<(&'a K, &'a V)>;
+    type Item = (&'a K, &'a V);
+    fn into_par_iter(self) -> Self::Iter {
+        self.par_iter()
+    }
+}
+
+impl<K: Ord + Send, V: Send, P: Policy> FromParallelIterator<(K, V)> for PinnedMap<K, V, P> {
+    fn from_par_iter<I: IntoParallelIterator<Item = (K, V)>>(par_iter: I) -> Self {
+        let map = Self::default();
+        (&map).par_extend(par_iter);
+        map
+    }
+}
+
+impl<K: Ord + Send, V: Send, P: Policy> ParallelExtend<(K, V)> for PinnedMap<K, V, P> {
+    fn par_extend<I: IntoParallelIterator<Item = (K, V)>>(&mut self, par_iter: I) {
+        (&*self).par_extend(par_iter)
+    }
+}
+
+impl<K: Ord + Send, V: Send, P: Policy> ParallelExtend<(K, V)> for &PinnedMap<K, V, P> {
+    /// Items are inserted in the order of `par_iter`,
+    /// and the [Policy] is applied to each of them.
+    ///
+    /// The outputs of the [Policy] are dropped,
+    /// including the [DuplicateKey](crate::DuplicateKey) errors of [ReturnError](crate::ReturnError).
+    /// Collect `par_iter` and call [PinnedMap::extend_refs] to get them.
+    fn par_extend<I: IntoParallelIterator<Item = (K, V)>>(&mut self, par_iter: I) {
+        // Collected on each thread first, so that the lock is taken once,
+        // and only released to apply the policy to a duplicate key.
+        let chunks = par_iter.into_par_iter().collect_vec_list();
+        self.extend_refs::<_, Vec<_>>(chunks.into_iter().flatten());
+    }
+}
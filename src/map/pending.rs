@@ -0,0 +1,273 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+#[cfg(feature = "async")]
+use core::{
+    cell::RefCell,
+    future::{poll_fn, Future},
+    pin::pin,
+    task::{Context, Poll},
+};
+use core::{fmt::Debug, mem, ops::Deref, sync::atomic::AtomicU64, task::Waker};
+use std::{
+    collections::BTreeMap,
+    sync::{Condvar, Mutex, MutexGuard, PoisonError, RwLock},
+    thread::{self, ThreadId},
+};
+
+use super::{
+    subscribe::{self, Event, Subscribers},
+    PinnedMap, Slot,
+};
+use crate::{notify::Notify, PANIC};
+
+/// Keys whose initializers are running, with their slots.
+///
+/// Keys are boxed, so that a [Claimed] can refer to its key
+/// while the list is modified by others.
+/// They are kept out of the shared [Pending],
+/// so that the map is [Send] under the same bounds as a [BTreeMap].
+pub(super) type List<K> = Vec<(Box<K>, Arc<Pending>)>;
+
+/// Slot of an initializer in progress.
+///
+/// Callers asking for the same key wait on this slot
+/// instead of running their own initializers.
+pub(super) struct Pending {
+    state: Mutex<State>,
+    cond: Condvar,
+    /// The thread running a blocking initializer, if any.
+    owner: Option<ThreadId>,
+}
+
+#[cfg(feature = "async")]
+std::thread_local! {
+    /// Slots whose async initializers are being polled on this thread, innermost last.
+    static POLLING: RefCell<Vec<*const ()>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Pops the innermost slot from [POLLING], even if polling panics.
+#[cfg(feature = "async")]
+struct Polling;
+
+#[cfg(feature = "async")]
+impl Drop for Polling {
+    fn drop(&mut self) {
+        POLLING.with(|p| p.borrow_mut().pop());
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    done: bool,
+    wakers: Vec<Waker>,
+}
+
+impl Pending {
+    fn new(owner: Option<ThreadId>) -> Self {
+        Self {
+            state: Mutex::default(),
+            cond: Condvar::new(),
+            owner,
+        }
+    }
+    /// Check if the async initializer of this slot is being polled on the current thread,
+    /// in which case the caller is part of it.
+    fn is_polling(&self) -> bool {
+        #[cfg(feature = "async")]
+        {
+            let ptr = self as *const Self as *const ();
+            POLLING.with(|p| p.borrow().contains(&ptr))
+        }
+        #[cfg(not(feature = "async"))]
+        false
+    }
+    /// Mark the initializer as finished, either fulfilled or cancelled,
+    /// and wake up all waiters.
+    fn finish(&self) {
+        let wakers = {
+            let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+            state.done = true;
+            mem::take(&mut state.wakers)
+        };
+        self.cond.notify_all();
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+    /// Block the current thread until the initializer is finished.
+    pub(super) fn wait(&self) {
+        let state = self.state.lock().expect(PANIC);
+        let _state = self.cond.wait_while(state, |s| !s.done).expect(PANIC);
+    }
+    /// Poll until the initializer is finished.
+    #[cfg(feature = "async")]
+    pub(super) fn poll_done(&self, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.state.lock().expect(PANIC);
+        if state.done {
+            return Poll::Ready(());
+        }
+        if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+impl Debug for Pending {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pending")
+            .field("state", &self.state)
+            .field("owner", &self.owner)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Result of trying to claim the initialization of a key.
+pub(super) enum Claim<'a, K: Ord, V> {
+    /// The key is already present. The key is handed back.
+    Ready(&'a V, K),
+    /// Another initializer is in progress. The key is handed back.
+    Wait(Arc<Pending>, K),
+    /// The caller shall run the initializer.
+    Init(Claimed<'a, K, V>),
+}
+
+/// Ownership of a [Pending] slot.
+///
+/// Dropping it without [fulfilling](Claimed::fulfill) it
+/// (for example, when the initializer is cancelled or panics)
+/// removes the slot, so that one of the waiters can take over.
+pub(super) struct Claimed<'a, K: Ord, V> {
+    sections: &'a RwLock<BTreeMap<K, Slot<V>>>,
+    next_seq: &'a AtomicU64,
+    notify: &'a Notify,
+    subscribers: &'a Mutex<Subscribers<K, V>>,
+    pending: &'a Mutex<List<K>>,
+    slot: Arc<Pending>,
+}
+
+impl<'a, K: Ord, V> Claimed<'a, K, V> {
+    /// Get the claimed key.
+    pub(super) fn key(&self) -> &K {
+        let pending = self.pending.lock().expect(PANIC);
+        let key: &K = &pending[self.position(&pending)].0;
+        // The key is boxed, and only removed from the list by `self`.
+        unsafe { mem::transmute::<&K, &K>(key) }
+    }
+    /// Give up the claim and take the key back.
+    pub(super) fn into_key(self) -> K {
+        let mut pending = self.pending.lock().expect(PANIC);
+        self.take(&mut pending)
+    }
+    /// Insert the value produced by the initializer.
+    ///
+    /// The value is dropped if the key has been inserted in the meantime,
+    /// for example by [PinnedMap::insert].
+    pub(super) fn fulfill(self, value: V) -> &'a V {
+        self.fulfill_full(value).0
+    }
+    /// The same as [Claimed::fulfill],
+    /// but also hands the key back if it was not inserted.
+    pub(super) fn fulfill_full(self, value: V) -> (&'a V, Option<K>) {
+        let _resume = subscribe::Resume;
+        let mut pending = self.pending.lock().expect(PANIC);
+        let key = self.take(&mut pending);
+        let mut guard = self.sections.write().expect(PANIC);
+        if let Some(v) = guard.get(&key) {
+            let r = v.deref();
+            return (unsafe { mem::transmute::<&V, &V>(r) }, Some(key));
+        }
+        let item = Slot::new(value, self.next_seq);
+        let r = item.deref();
+        let r: &V = unsafe { mem::transmute::<&V, &V>(r) };
+        subscribe::publish(self.subscribers, Event::Inserted(&key, r));
+        guard.insert(key, item);
+        drop(guard);
+        drop(pending);
+        self.notify.notify();
+        (r, None)
+    }
+    /// Run the async initializer,
+    /// marking the slot as being polled on the current thread meanwhile.
+    #[cfg(feature = "async")]
+    pub(super) async fn run<F, Fut>(&self, default: F) -> Fut::Output
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future,
+    {
+        let mut default = Some(default);
+        let mut future = pin!(None::<Fut>);
+        let ptr = Arc::as_ptr(&self.slot) as *const ();
+        poll_fn(|cx| {
+            POLLING.with(|p| p.borrow_mut().push(ptr));
+            let _polling = Polling;
+            if let Some(default) = default.take() {
+                future.set(Some(default()));
+            }
+            let future = future.as_mut().as_pin_mut();
+            future.expect("internal error: missing future").poll(cx)
+        })
+        .await
+    }
+    /// Find the slot in the pending list.
+    fn position(&self, pending: &List<K>) -> usize {
+        let index = pending.iter().position(|(_, s)| Arc::ptr_eq(s, &self.slot));
+        index.expect("internal error: missing slot")
+    }
+    /// Remove the slot from the pending list and take the key out of it.
+    fn take(&self, pending: &mut MutexGuard<'_, List<K>>) -> K {
+        let index = self.position(pending);
+        *pending.swap_remove(index).0
+    }
+}
+
+impl<K: Ord, V> Drop for Claimed<'_, K, V> {
+    fn drop(&mut self) {
+        {
+            let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+            if pending.iter().any(|(_, s)| Arc::ptr_eq(s, &self.slot)) {
+                drop(self.take(&mut pending));
+            }
+        }
+        self.slot.finish();
+    }
+}
+
+impl<K: Ord, V, P> PinnedMap<K, V, P> {
+    /// Look up the key, or claim its initialization if nobody else did.
+    ///
+    /// Blocking callers pass `true` for `blocking`,
+    /// so that waiting for their own initializer is reported
+    /// instead of deadlocking.
+    /// So is waiting for an async initializer from inside of it.
+    ///
+    /// Present keys are looked up under the read lock only.
+    /// Otherwise, the pending list is locked before the map is checked again,
+    /// so that a finished initializer can't be missed.
+    pub(super) fn claim(&self, key: K, blocking: bool) -> Claim<'_, K, V> {
+        if let Some(v) = self.get(&key) {
+            return Claim::Ready(v, key);
+        }
+        let owner = blocking.then(|| thread::current().id());
+        let mut pending = self.pending.lock().expect(PANIC);
+        if let Some(v) = self.get(&key) {
+            return Claim::Ready(v, key);
+        }
+        if let Some((_, slot)) = pending.iter().find(|(k, _)| **k == key) {
+            if owner.is_some() && slot.owner == owner || slot.is_polling() {
+                panic!("reentrant initialization of the same key");
+            }
+            return Claim::Wait(slot.clone(), key);
+        }
+        let slot = Arc::new(Pending::new(owner));
+        pending.push((Box::new(key), slot.clone()));
+        Claim::Init(Claimed {
+            sections: &self.sections,
+            next_seq: &self.next_seq,
+            notify: &self.notify,
+            subscribers: &self.subscribers,
+            pending: &self.pending,
+            slot,
+        })
+    }
+}
@@ -0,0 +1,66 @@
+use std::{fmt::Debug, iter::FusedIterator, vec};
+
+use super::{erase, Slot};
+
+/// A shadowed item with its key,
+/// and the sequence number of the item shadowing it.
+pub(super) type Item<K, V> = (Box<K>, Slot<V>, u64);
+
+/// Iterator over shadowed key-value pairs of [super::PinnedMap].
+///
+/// It iterates over a snapshot taken on creation,
+/// so that no lock is held while iterating.
+pub struct Shadowed<'a, K, V> {
+    inner: vec::IntoIter<(&'a K, &'a V)>,
+}
+
+impl<'a, K, V> Shadowed<'a, K, V> {
+    /// Keys and items are boxed, and never removed through a shared reference,
+    /// so they outlive the lock guarding the list.
+    pub(super) fn new(items: &[Item<K, V>]) -> Self {
+        // Only the boxes are referred to after this.
+        let items = unsafe { std::mem::transmute::<&[Item<K, V>], &'a [Item<K, V>]>(items) };
+        let inner = items
+            .iter()
+            .map(|(k, v, _)| (erase(k), erase(v)))
+            .collect::<Vec<_>>()
+            .into_iter();
+        Self { inner }
+    }
+}
+
+impl<'a, K: 'a, V: 'a> Iterator for Shadowed<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn last(mut self) -> Option<(&'a K, &'a V)> {
+        self.next_back()
+    }
+}
+
+impl<'a, K: 'a, V: 'a> DoubleEndedIterator for Shadowed<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        self.inner.next_back()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Shadowed<'_, K, V> {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+impl<K, V> FusedIterator for Shadowed<'_, K, V> {}
+
+impl<K: Debug, V: Debug> Debug for Shadowed<'_, K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.inner.as_slice()).finish()
+    }
+}
@@ -0,0 +1,174 @@
+use super::PinnedMap;
+use crate::PANIC;
+use alloc::{boxed::Box, vec::Vec};
+use core::{any::Any, cell::Cell, fmt::Debug};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{Mutex, PoisonError},
+    thread,
+};
+
+/// A change of [PinnedMap] delivered to subscribers.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Event<'a, K, V> {
+    /// A new key has been inserted.
+    Inserted(&'a K, &'a V),
+    /// An item has been inserted under a present key,
+    /// shadowing the existing one.
+    Shadowed {
+        /// The key of both items.
+        key: &'a K,
+        /// The inserted item.
+        value: &'a V,
+        /// The item shadowed, which is still alive.
+        shadowed: &'a V,
+    },
+}
+
+impl<K, V> Clone for Event<'_, K, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K, V> Copy for Event<'_, K, V> {}
+
+type Callback<K, V> = Box<dyn Fn(Event<'_, K, V>) + Send + Sync>;
+
+/// Callbacks registered by [PinnedMap::subscribe].
+///
+/// It has its own lock, so that a [Subscription] can be dropped
+/// without locking the map.
+pub(super) struct Subscribers<K, V> {
+    next_id: u64,
+    callbacks: Vec<(u64, Callback<K, V>)>,
+}
+
+impl<K, V> Subscribers<K, V> {
+    pub(super) const fn new() -> Self {
+        Self {
+            next_id: 0,
+            callbacks: Vec::new(),
+        }
+    }
+}
+
+impl<K, V> Debug for Subscribers<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Subscribers")
+            .field("len", &self.callbacks.len())
+            .finish_non_exhaustive()
+    }
+}
+
+std::thread_local! {
+    /// The first panic caught from callbacks on this thread,
+    /// which is resumed by [Resume].
+    static PANICKED: Cell<Option<Box<dyn Any + Send>>> = const { Cell::new(None) };
+}
+
+/// Deliver `event` to all subscribers.
+///
+/// Shall be called under the write lock of the map,
+/// before the item is visible to readers,
+/// and with a [Resume] alive.
+///
+/// Callbacks are not allowed to panic through,
+/// which would poison the map.
+pub(super) fn publish<K, V>(subscribers: &Mutex<Subscribers<K, V>>, event: Event<'_, K, V>) {
+    let subscribers = subscribers.lock().expect(PANIC);
+    for (_, f) in &subscribers.callbacks {
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| f(event))) {
+            PANICKED.with(|p| {
+                let first = p.take().unwrap_or(payload);
+                p.set(Some(first));
+            });
+        }
+    }
+}
+
+/// Resumes the panic caught by [publish] when dropped,
+/// so it shall be created before locking the map.
+pub(super) struct Resume;
+
+impl Drop for Resume {
+    fn drop(&mut self) {
+        if let Some(payload) = PANICKED.with(Cell::take) {
+            // Already unwinding from another panic.
+            if !thread::panicking() {
+                panic::resume_unwind(payload);
+            }
+        }
+    }
+}
+
+/// Registration of a callback by [PinnedMap::subscribe],
+/// which is removed on drop.
+#[must_use = "the callback is removed when the subscription is dropped"]
+pub struct Subscription<'a, K, V, P> {
+    map: &'a PinnedMap<K, V, P>,
+    id: u64,
+}
+
+impl<K, V, P> PinnedMap<K, V, P> {
+    /// Call `f` on every item inserted from now on,
+    /// until the returned [Subscription] is dropped.
+    ///
+    /// `f` runs under the write lock of the [PinnedMap],
+    /// before the inserting call returns.
+    /// So events are delivered one at a time, in the order of insertion,
+    /// and items inserted before [PinnedMap::subscribe] is called are never delivered,
+    /// while all items inserted after it returns are.
+    /// For the same reason, `f` shall not access the [PinnedMap]
+    /// or drop its own [Subscription], which deadlocks.
+    ///
+    /// If `f` panics, the item is inserted and delivered to other subscribers anyway,
+    /// and the panic is resumed in the inserting call once the [PinnedMap] is unlocked.
+    ///
+    /// Items shadowing existing ones are delivered as [Event::Shadowed].
+    /// Items rejected by the [Policy](crate::Policy) are not delivered.
+    ///
+    /// ```rust
+    /// use pinned_bucket::*;
+    /// use std::sync::{Arc, Mutex};
+    /// let v = PinnedMap::new();
+    /// v.insert(0, 'z');
+    /// let events = Arc::new(Mutex::new(Vec::new()));
+    /// let log = events.clone();
+    /// let sub = v.subscribe(move |e| {
+    ///     log.lock().unwrap().push(match e {
+    ///         Event::Inserted(k, v) => (*k, *v, None),
+    ///         Event::Shadowed { key, value, shadowed } => (*key, *value, Some(*shadowed)),
+    ///     })
+    /// });
+    /// v.insert(1, 'a');
+    /// v.insert(1, 'b');
+    /// drop(sub);
+    /// v.insert(2, 'c');
+    /// assert_eq!(*events.lock().unwrap(), [(1, 'a', None), (1, 'b', Some('a'))]);
+    /// ```
+    pub fn subscribe<F>(&self, f: F) -> Subscription<'_, K, V, P>
+    where
+        F: Fn(Event<'_, K, V>) + Send + Sync + 'static,
+    {
+        let mut subscribers = self.subscribers.lock().expect(PANIC);
+        let id = subscribers.next_id;
+        subscribers.next_id += 1;
+        subscribers.callbacks.push((id, Box::new(f)));
+        Subscription { map: self, id }
+    }
+}
+
+impl<K, V, P> Drop for Subscription<'_, K, V, P> {
+    fn drop(&mut self) {
+        // Waits for the event being delivered, if any.
+        let mut subscribers = (self.map.subscribers.lock()).unwrap_or_else(PoisonError::into_inner);
+        subscribers.callbacks.retain(|(id, _)| *id != self.id);
+    }
+}
+
+impl<K, V, P> Debug for Subscription<'_, K, V, P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Subscription").field(&self.id).finish()
+    }
+}
@@ -2,23 +2,26 @@ use std::{
     collections::{btree_map as map, BTreeMap},
     fmt::Debug,
     iter::FusedIterator,
-    pin::Pin,
     sync::RwLockReadGuard,
 };
 
-use super::erase;
+use super::{erase, Slot};
 
 /// Iterator over values of [super::PinnedMap].
 pub struct Values<'a, K, V> {
     /// Shall not be read. Only kept here to prevent the map from being modified.
     #[allow(unused)]
-    guard: RwLockReadGuard<'a, BTreeMap<K, Pin<Box<V>>>>,
-    inner: map::Values<'a, K, Pin<Box<V>>>,
+    guard: RwLockReadGuard<'a, BTreeMap<K, Slot<V>>>,
+    inner: map::Values<'a, K, Slot<V>>,
 }
 
 impl<'a, K, V> Values<'a, K, V> {
-    pub(super) fn new(guard: RwLockReadGuard<'a, BTreeMap<K, Pin<Box<V>>>>) -> Self {
-        let inner = unsafe { std::mem::transmute(guard.values()) };
+    pub(super) fn new(guard: RwLockReadGuard<'a, BTreeMap<K, Slot<V>>>) -> Self {
+        let inner = unsafe {
+            std::mem::transmute::<map::Values<'_, K, Slot<V>>, map::Values<'a, K, Slot<V>>>(
+                guard.values(),
+            )
+        };
         Self { guard, inner }
     }
 }
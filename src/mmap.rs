@@ -0,0 +1,291 @@
+//! A list of plain data living in memory-mapped chunks,
+//! so that it may grow beyond the RAM and persist across runs.
+//!
+//! It is not a backend of [PinnedList](crate::PinnedList),
+//! but a separate type with a smaller API.
+//!
+//! Only available with the `mmap` feature.
+
+use crate::PANIC;
+use alloc::vec::Vec;
+use core::{marker::PhantomData, mem, ops::Index, ptr::NonNull};
+use memmap2::{MmapMut, MmapOptions};
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    path::Path,
+    sync::RwLock,
+};
+
+/// Types that can be stored as raw bytes and read back.
+///
+/// # Safety
+///
+/// Implementors shall have no padding, no pointers or references,
+/// and every bit pattern shall be a valid value,
+/// including all zeros.
+/// The layout shall not change between runs reading the same file.
+pub unsafe trait PlainData: Copy + Send + Sync + 'static {}
+
+macro_rules! plain_data {
+    ($($t:ty),*) => {
+        $(unsafe impl PlainData for $t {})*
+    };
+}
+
+plain_data!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<T: PlainData, const N: usize> PlainData for [T; N] {}
+
+/// Alignment of offsets in the file, which is the largest allocation granularity
+/// of supported platforms.
+const ALIGN: usize = 1 << 16;
+
+/// Preferred size of each chunk in bytes.
+const CHUNK: usize = 1 << 20;
+
+/// Leading bytes of the file, followed by the size of `T` and the length.
+const MAGIC: u64 = u64::from_le_bytes(*b"pinnedbk");
+
+/// A mapping of a fixed number of items.
+#[derive(Debug)]
+struct Chunk {
+    /// Only flushed, never dereferenced,
+    /// so that the items are not aliased by `&mut [u8]`.
+    map: MmapMut,
+    ptr: NonNull<u8>,
+}
+
+impl Chunk {
+    fn new(mut map: MmapMut) -> Self {
+        let ptr = NonNull::new(map.as_mut_ptr()).expect("internal error: null mapping");
+        Self { map, ptr }
+    }
+}
+
+#[derive(Debug)]
+struct Store {
+    chunks: Vec<Chunk>,
+    len: usize,
+    /// The backing file and its header, unless anonymous.
+    file: Option<(File, Chunk)>,
+}
+
+/// A list of [PlainData] in memory-mapped chunks of the same size.
+///
+/// Chunks are never remapped once mapped,
+/// so references to items stay valid while more items are pushed.
+///
+/// It is a separate type, not a storage backend of
+/// [PinnedList](crate::PinnedList),
+/// and only offers pushing, indexing and flushing.
+/// Iteration, cursors, checkpoints and waiting are not available.
+///
+/// ```rust
+/// use pinned_bucket::mmap::*;
+/// # fn main() -> std::io::Result<()> {
+/// let path = std::env::temp_dir().join(format!("pinned-bucket-doc-{}.mmap", std::process::id()));
+/// # let _ = std::fs::remove_file(&path);
+/// let v = MmapList::<[u32; 2]>::open(&path)?;
+/// let a = v.push([1, 2])?;
+/// v.push([3, 4])?;
+/// assert_eq!(a, &[1, 2]);
+/// v.flush()?;
+/// drop(v);
+///
+/// let v = MmapList::<[u32; 2]>::open(&path)?;
+/// assert_eq!(v.len(), 2);
+/// assert_eq!(v[1], [3, 4]);
+/// # drop(v);
+/// # std::fs::remove_file(&path)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MmapList<T> {
+    store: RwLock<Store>,
+    /// Size of each chunk in bytes.
+    chunk_bytes: usize,
+    item: PhantomData<T>,
+}
+
+// Items are only written before they are handed out.
+unsafe impl<T: PlainData> Send for MmapList<T> {}
+unsafe impl<T: PlainData> Sync for MmapList<T> {}
+
+impl<T: PlainData> MmapList<T> {
+    fn with_store(store: Store) -> Self {
+        assert!(
+            mem::size_of::<T>() > 0,
+            "zero-sized types are not supported"
+        );
+        let chunk_bytes = CHUNK.max(mem::size_of::<T>()).next_multiple_of(ALIGN);
+        Self {
+            store: RwLock::new(store),
+            chunk_bytes,
+            item: PhantomData,
+        }
+    }
+    /// Number of items in each chunk.
+    fn chunk_len(&self) -> usize {
+        self.chunk_bytes / mem::size_of::<T>()
+    }
+    /// Create an empty [MmapList] in anonymous mappings,
+    /// which can be swapped out, but is gone on drop.
+    ///
+    /// Nothing is mapped until the first push.
+    pub fn anonymous() -> Self {
+        Self::with_store(Store {
+            chunks: Vec::new(),
+            len: 0,
+            file: None,
+        })
+    }
+    /// Open the [MmapList] stored in the file at `path`, or create an empty one.
+    ///
+    /// Items are written to the file by the OS at any time,
+    /// while the length is only guaranteed to be written by [MmapList::flush].
+    /// So items pushed after that may be lost, or read back as zeros,
+    /// after a crash of the system, but the items before are kept intact.
+    ///
+    /// The file is locked exclusively until the [MmapList] is dropped,
+    /// as items pushed by two of them would overwrite each other.
+    ///
+    /// Fails with [io::ErrorKind::InvalidData] if the file is not an [MmapList]
+    /// of items of the same size,
+    /// and with [io::ErrorKind::WouldBlock] if it is already open.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        file.try_lock()?;
+        let size = mem::size_of::<T>() as u64;
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a list of items of this size",
+            )
+        };
+        match file.metadata()?.len() {
+            0 => file.set_len(ALIGN as u64)?,
+            l if l < ALIGN as u64 => return Err(invalid()),
+            _ => {}
+        }
+        let header = Chunk::new(unsafe { MmapOptions::new().len(ALIGN).map_mut(&file)? });
+        let fields = header.ptr.cast::<[u64; 3]>();
+        // Mappings are aligned to pages.
+        let [magic, item_size, len] = unsafe { fields.read() }.map(u64::from_le);
+        let len = match (magic, item_size) {
+            (0, 0) => {
+                unsafe { fields.write([MAGIC, size, 0].map(u64::to_le)) };
+                0
+            }
+            (MAGIC, s) if s == size => len as usize,
+            _ => return Err(invalid()),
+        };
+        let mut list = Self::with_store(Store {
+            chunks: Vec::new(),
+            len,
+            file: Some((file, header)),
+        });
+        let (chunks, chunk_bytes) = (len.div_ceil(list.chunk_len()), list.chunk_bytes);
+        let store = list.store.get_mut().expect(PANIC);
+        for _ in 0..chunks {
+            let chunk = map_chunk(store, chunk_bytes)?;
+            store.chunks.push(chunk);
+        }
+        Ok(list)
+    }
+    /// Get the number of items.
+    pub fn len(&self) -> usize {
+        self.store.read().expect(PANIC).len
+    }
+    /// Check if the [MmapList] is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Push an item into the [MmapList]
+    /// and return the reference to it.
+    ///
+    /// Fails if a new chunk can't be mapped.
+    pub fn push(&self, t: T) -> io::Result<&T> {
+        let mut store = self.store.write().expect(PANIC);
+        let (chunk, offset) = (store.len / self.chunk_len(), store.len % self.chunk_len());
+        if chunk == store.chunks.len() {
+            let new = map_chunk(&store, self.chunk_bytes)?;
+            store.chunks.push(new);
+        }
+        let ptr = unsafe { self.item_ptr(&store, chunk, offset) };
+        // Nobody else refers to the slots after the length.
+        unsafe { ptr.write(t) };
+        store.len += 1;
+        if let Some((_, header)) = &store.file {
+            let len = header.ptr.cast::<u64>().as_ptr().wrapping_add(2);
+            unsafe { len.write((store.len as u64).to_le()) };
+        }
+        Ok(unsafe { &*ptr })
+    }
+    /// Get an item in [MmapList].
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let store = self.store.read().expect(PANIC);
+        if index >= store.len {
+            return None;
+        }
+        let ptr =
+            unsafe { self.item_ptr(&store, index / self.chunk_len(), index % self.chunk_len()) };
+        // Mapped chunks live as long as `self`.
+        Some(unsafe { &*ptr })
+    }
+    /// Write the items and the length to the file.
+    ///
+    /// It does nothing if the [MmapList] is anonymous.
+    pub fn flush(&self) -> io::Result<()> {
+        let store = self.store.read().expect(PANIC);
+        let Some((_, header)) = &store.file else {
+            return Ok(());
+        };
+        for chunk in &store.chunks {
+            chunk.map.flush()?;
+        }
+        header.map.flush()
+    }
+    /// # Safety
+    ///
+    /// The chunk shall be mapped.
+    unsafe fn item_ptr(&self, store: &Store, chunk: usize, offset: usize) -> *mut T {
+        let base = store.chunks[chunk].ptr.as_ptr();
+        unsafe { base.add(offset * mem::size_of::<T>()) }.cast::<T>()
+    }
+}
+
+/// Map the chunk after the last one, growing the file if any.
+fn map_chunk(store: &Store, chunk_bytes: usize) -> io::Result<Chunk> {
+    let map = match &store.file {
+        None => MmapMut::map_anon(chunk_bytes)?,
+        Some((file, _)) => {
+            let offset = (ALIGN + store.chunks.len() * chunk_bytes) as u64;
+            let end = offset + chunk_bytes as u64;
+            if file.metadata()?.len() < end {
+                file.set_len(end)?;
+            }
+            // The file is not expected to be truncated by others.
+            unsafe {
+                MmapOptions::new()
+                    .offset(offset)
+                    .len(chunk_bytes)
+                    .map_mut(file)?
+            }
+        }
+    };
+    Ok(Chunk::new(map))
+}
+
+impl<T: PlainData> Index<usize> for MmapList<T> {
+    type Output = T;
+    fn index(&self, index: usize) -> &T {
+        self.get(index).expect("index out of bounds")
+    }
+}
@@ -0,0 +1,195 @@
+use super::PANIC;
+use alloc::boxed::Box;
+use core::{borrow::Borrow, mem, ops::Deref, pin::Pin};
+use std::{collections::BTreeMap, fmt::Debug, sync::RwLock};
+
+pub use get_all::GetAll;
+
+mod get_all;
+
+type Sections<K, V> = BTreeMap<K, Vec<Pin<Box<V>>>>;
+
+/// A map from `K` to a list of `Pin<Box<V>>`.
+///
+/// Inserting a key already present appends the item
+/// instead of shadowing the previous ones,
+/// and all of them remain queryable in insertion order.
+///
+/// ```rust
+/// use pinned_bucket::*;
+/// let v = PinnedMultiMap::new();
+/// let a = v.insert(1, 2);
+/// let b = v.insert(1, 3);
+/// assert_eq!(v.get(&1), Some(b));
+/// assert_eq!(v.get_all(&1).collect::<Vec<_>>(), [a, b]);
+/// ```
+///
+/// As the items inside are still managed by the container,
+/// codes below won't compile.
+///
+/// ```compile_fail
+/// use pinned_bucket::*;
+/// let v = PinnedMultiMap::new();
+/// let a = v.insert(1, 2);
+/// drop(v);
+/// assert_eq!(a, &2);
+/// ```
+///
+/// If you [clone](Clone::clone) this,
+/// references to items in new container will be different to
+/// references to those in old container.
+#[derive(Debug)]
+pub struct PinnedMultiMap<K, V> {
+    sections: RwLock<Sections<K, V>>,
+}
+impl<K, V> Default for PinnedMultiMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<K, V> PinnedMultiMap<K, V> {
+    /// Create an empty [PinnedMultiMap].
+    pub const fn new() -> Self {
+        Self {
+            sections: RwLock::new(BTreeMap::new()),
+        }
+    }
+    /// Get the number of distinct keys in [PinnedMultiMap].
+    pub fn len(&self) -> usize {
+        self.sections.read().expect(PANIC).len()
+    }
+    /// Check if the [PinnedMultiMap] is empty.
+    pub fn is_empty(&self) -> bool {
+        self.sections.read().expect(PANIC).is_empty()
+    }
+    /// Check if the [PinnedMultiMap] contains a key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        self.sections.read().expect(PANIC).contains_key(key)
+    }
+    /// Get the number of items under a key.
+    pub fn count<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        self.sections
+            .read()
+            .expect(PANIC)
+            .get(key)
+            .map_or(0, Vec::len)
+    }
+    /// Push an item into the [PinnedMultiMap]
+    /// after those with the same key,
+    /// and return the reference to it.
+    pub fn insert(&self, key: K, value: V) -> &V
+    where
+        K: Ord,
+    {
+        let item = Box::pin(value);
+        let r = item.deref();
+        let r: &V = unsafe { mem::transmute::<&V, &V>(r) };
+        self.sections
+            .write()
+            .expect(PANIC)
+            .entry(key)
+            .or_default()
+            .push(item);
+        r
+    }
+    /// Get the latest item under a key.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        let guard = self.sections.read().expect(PANIC);
+        guard.get(key).and_then(|v| v.last()).map(|v| {
+            let r = v.deref();
+            unsafe { mem::transmute::<&V, &V>(r) }
+        })
+    }
+    /// Get all items under a key, in insertion order.
+    pub fn get_all<Q>(&self, key: &Q) -> GetAll<'_, K, V>
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        let guard = self.sections.read().expect(PANIC);
+        GetAll::new(guard, key)
+    }
+}
+impl<K: Clone, V: Clone> Clone for PinnedMultiMap<K, V> {
+    fn clone(&self) -> Self {
+        let values = self.sections.read().expect(PANIC);
+        let sections = values.clone().into();
+        Self { sections }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let v = PinnedMultiMap::new();
+        let a = v.insert(1, "a".to_owned());
+        let b = v.insert(2, "b".to_owned());
+        let c = v.insert(1, "c".to_owned());
+
+        assert_eq!(v.len(), 2);
+        assert_eq!(v.count(&1), 2);
+        assert_eq!(v.count(&3), 0);
+        assert!(v.contains_key(&2));
+        assert!(!v.contains_key(&3));
+
+        assert_eq!(v.get(&1), Some(c));
+        assert_eq!(v.get(&2), Some(b));
+        assert_eq!(v.get(&3), None);
+
+        let all: Vec<_> = v.get_all(&1).map(|s| s as *const String).collect();
+        assert_eq!(all, [a as *const String, c as *const String]);
+        assert_eq!(v.get_all(&1).len(), 2);
+        assert_eq!(v.get_all(&3).next(), None);
+        assert_eq!(format!("{:?}", v.get_all(&1)), r#"["a", "c"]"#);
+    }
+
+    #[test]
+    fn borrowed_keys() {
+        let v = PinnedMultiMap::new();
+        v.insert("a".to_owned(), 1);
+        v.insert("a".to_owned(), 2);
+        assert!(v.contains_key("a"));
+        assert_eq!(v.count("a"), 2);
+        assert_eq!(v.get("a"), Some(&2));
+        assert_eq!(v.get_all("a").collect::<Vec<_>>(), [&1, &2]);
+        assert_eq!(v.get("b"), None);
+    }
+
+    #[test]
+    fn insert_while_iter() {
+        let v = PinnedMultiMap::new();
+        v.insert(1, 1);
+        v.insert(1, 2);
+        let all: Vec<_> = v.get_all(&1).collect();
+        for i in 0..16 {
+            v.insert(1, i);
+        }
+        assert_eq!(all, [&1, &2]);
+        assert_eq!(v.count(&1), 18);
+        assert_eq!(v.get_all(&1).next_back(), Some(&15));
+    }
+
+    #[test]
+    fn debug_map() {
+        let v: PinnedMultiMap<usize, usize> = PinnedMultiMap::default();
+        v.insert(1, 2);
+        v.insert(1, 3);
+        let u = v.clone();
+        assert_eq!(format!("{:?}", v), format!("{:?}", u));
+    }
+}
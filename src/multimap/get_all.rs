@@ -0,0 +1,72 @@
+use std::{
+    borrow::Borrow, fmt::Debug, iter::FusedIterator, pin::Pin, slice, sync::RwLockReadGuard,
+};
+
+use super::Sections;
+
+/// Iterator over items under a key of [super::PinnedMultiMap].
+pub struct GetAll<'a, K, V> {
+    /// Shall not be read. Only kept here to prevent the list from being reallocated.
+    #[allow(unused)]
+    guard: RwLockReadGuard<'a, Sections<K, V>>,
+    inner: slice::Iter<'a, Pin<Box<V>>>,
+}
+
+impl<'a, K: Ord, V> GetAll<'a, K, V> {
+    pub(super) fn new<Q>(guard: RwLockReadGuard<'a, Sections<K, V>>, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let items = guard.get(key).map_or(&[][..], Vec::as_slice);
+        let inner = unsafe {
+            std::mem::transmute::<slice::Iter<'_, Pin<Box<V>>>, slice::Iter<'a, Pin<Box<V>>>>(
+                items.iter(),
+            )
+        };
+        Self { guard, inner }
+    }
+}
+
+fn erase<'a, V>(v: &Pin<Box<V>>) -> &'a V {
+    let r: &V = v;
+    unsafe { std::mem::transmute::<&V, &V>(r) }
+}
+
+impl<'a, K, V: 'a> Iterator for GetAll<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.inner.next().map(erase)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn last(mut self) -> Option<&'a V> {
+        self.next_back()
+    }
+}
+
+impl<'a, K, V: 'a> DoubleEndedIterator for GetAll<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a V> {
+        self.inner.next_back().map(erase)
+    }
+}
+
+impl<K, V> ExactSizeIterator for GetAll<'_, K, V> {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+impl<K, V> FusedIterator for GetAll<'_, K, V> {}
+
+impl<K, V: Debug> Debug for GetAll<'_, K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.inner.clone().map(|v| &**v))
+            .finish()
+    }
+}
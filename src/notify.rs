@@ -0,0 +1,131 @@
+use super::PANIC;
+use alloc::vec::Vec;
+#[cfg(feature = "async")]
+use core::task::{Context, Poll};
+use core::{
+    mem,
+    sync::atomic::{fence, AtomicUsize, Ordering},
+    task::Waker,
+    time::Duration,
+};
+use std::{
+    sync::{Condvar, Mutex, PoisonError},
+    time::Instant,
+};
+
+/// Wakes up threads waiting for a container to change.
+///
+/// The lock of the container can't be paired with a [Condvar],
+/// so a separate [Mutex] is used.
+/// Waiters check the container while holding it,
+/// and writers take it after changing the container,
+/// so that no change is missed.
+///
+/// Async waiters leave their [Waker]s under the same [Mutex],
+/// which are taken away on the next change.
+#[derive(Debug, Default)]
+pub(crate) struct Notify {
+    /// Number of blocked threads and registered [Waker]s.
+    waiters: AtomicUsize,
+    lock: Mutex<Vec<Waker>>,
+    cond: Condvar,
+}
+
+/// Unregisters a waiter, even if it panics.
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Notify {
+    pub(crate) const fn new() -> Self {
+        Self {
+            waiters: AtomicUsize::new(0),
+            lock: Mutex::new(Vec::new()),
+            cond: Condvar::new(),
+        }
+    }
+    /// Wake up all waiters.
+    ///
+    /// Shall be called after the change is made,
+    /// and without holding the lock of the container.
+    pub(crate) fn notify(&self) {
+        // Pairs with the registration of waiters,
+        // so that either the waiter sees the change or the change sees the waiter.
+        fence(Ordering::SeqCst);
+        if self.waiters.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        let wakers = mem::take(&mut *self.lock.lock().unwrap_or_else(PoisonError::into_inner));
+        self.waiters.fetch_sub(wakers.len(), Ordering::SeqCst);
+        self.cond.notify_all();
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+    /// Block until `f` returns something, or `timeout` elapses.
+    pub(crate) fn wait<R>(
+        &self,
+        mut f: impl FnMut() -> Option<R>,
+        timeout: Option<Duration>,
+    ) -> Option<R> {
+        if let Some(r) = f() {
+            return Some(r);
+        }
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        self.waiters.fetch_add(1, Ordering::SeqCst);
+        let _waiting = Waiting(&self.waiters);
+        let mut guard = self.lock.lock().expect(PANIC);
+        loop {
+            if let Some(r) = f() {
+                return Some(r);
+            }
+            guard = match deadline {
+                None => self.cond.wait(guard).expect(PANIC),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return None;
+                    }
+                    self.cond
+                        .wait_timeout(guard, deadline - now)
+                        .expect(PANIC)
+                        .0
+                }
+            };
+        }
+    }
+    /// Poll until `f` returns something.
+    ///
+    /// The [Waker] is kept until the next change,
+    /// even if the future polling it is dropped.
+    #[cfg(feature = "async")]
+    pub(crate) fn poll_wait<R>(
+        &self,
+        cx: &mut Context<'_>,
+        f: impl FnOnce() -> Option<R>,
+    ) -> Poll<R> {
+        let mut wakers = self.lock.lock().expect(PANIC);
+        // Registered before checking, the same as blocking waiters.
+        self.waiters.fetch_add(1, Ordering::SeqCst);
+        if let Some(r) = f() {
+            self.waiters.fetch_sub(1, Ordering::SeqCst);
+            return Poll::Ready(r);
+        }
+        if wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            self.waiters.fetch_sub(1, Ordering::SeqCst);
+        } else {
+            wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+impl Clone for Notify {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
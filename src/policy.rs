@@ -0,0 +1,148 @@
+//! Policies on inserting duplicate keys into [PinnedMap](crate::PinnedMap).
+
+use core::fmt::{self, Debug, Display};
+use std::error::Error;
+
+/// What [PinnedMap::insert](crate::PinnedMap::insert) does with a duplicate key.
+///
+/// It is a type parameter of [PinnedMap](crate::PinnedMap),
+/// so maps with different policies can live in the same program.
+/// Values of it are only passed to pick the type, and never stored.
+pub trait Policy {
+    /// Output of [PinnedMap::insert](crate::PinnedMap::insert).
+    type Output<'a, K: 'a, V: 'a>;
+    /// The key was absent, and `value` has been inserted.
+    fn inserted<'a, K: 'a, V: 'a>(value: &'a V) -> Self::Output<'a, K, V>;
+    /// The key was present, and nothing has been inserted yet.
+    ///
+    /// `shadow` inserts the rejected item anyway,
+    /// shadowing the existing one.
+    fn duplicated<'a, K: 'a, V: 'a>(
+        duplicate: DuplicateKey<'a, K, V>,
+        shadow: impl FnOnce(K, V) -> &'a V,
+    ) -> Self::Output<'a, K, V>;
+}
+
+/// Shadow the existing item, which is kept alive.
+///
+/// This is the default policy.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Shadow;
+
+/// Panic.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Panic;
+
+/// Keep the existing item and drop the new one.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct KeepFirst;
+
+/// Return a [DuplicateKey] error, handing back the new item.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReturnError;
+
+impl Policy for Shadow {
+    type Output<'a, K: 'a, V: 'a> = &'a V;
+    fn inserted<'a, K: 'a, V: 'a>(value: &'a V) -> &'a V {
+        value
+    }
+    fn duplicated<'a, K: 'a, V: 'a>(
+        duplicate: DuplicateKey<'a, K, V>,
+        shadow: impl FnOnce(K, V) -> &'a V,
+    ) -> &'a V {
+        let (key, value) = duplicate.into_inner();
+        shadow(key, value)
+    }
+}
+
+impl Policy for Panic {
+    type Output<'a, K: 'a, V: 'a> = &'a V;
+    fn inserted<'a, K: 'a, V: 'a>(value: &'a V) -> &'a V {
+        value
+    }
+    fn duplicated<'a, K: 'a, V: 'a>(
+        _duplicate: DuplicateKey<'a, K, V>,
+        _shadow: impl FnOnce(K, V) -> &'a V,
+    ) -> &'a V {
+        panic!("duplicated key")
+    }
+}
+
+impl Policy for KeepFirst {
+    type Output<'a, K: 'a, V: 'a> = &'a V;
+    fn inserted<'a, K: 'a, V: 'a>(value: &'a V) -> &'a V {
+        value
+    }
+    fn duplicated<'a, K: 'a, V: 'a>(
+        duplicate: DuplicateKey<'a, K, V>,
+        _shadow: impl FnOnce(K, V) -> &'a V,
+    ) -> &'a V {
+        duplicate.existing()
+    }
+}
+
+impl Policy for ReturnError {
+    type Output<'a, K: 'a, V: 'a> = Result<&'a V, DuplicateKey<'a, K, V>>;
+    fn inserted<'a, K: 'a, V: 'a>(value: &'a V) -> Self::Output<'a, K, V> {
+        Ok(value)
+    }
+    fn duplicated<'a, K: 'a, V: 'a>(
+        duplicate: DuplicateKey<'a, K, V>,
+        _shadow: impl FnOnce(K, V) -> &'a V,
+    ) -> Self::Output<'a, K, V> {
+        Err(duplicate)
+    }
+}
+
+/// The error returned when inserting a key that is already present.
+///
+/// It hands back the rejected item.
+pub struct DuplicateKey<'a, K, V> {
+    key: K,
+    value: V,
+    existing: &'a V,
+}
+
+impl<'a, K, V> DuplicateKey<'a, K, V> {
+    pub(crate) fn new(key: K, value: V, existing: &'a V) -> Self {
+        Self {
+            key,
+            value,
+            existing,
+        }
+    }
+    /// Get the rejected key.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+    /// Get the rejected value.
+    pub fn value(&self) -> &V {
+        &self.value
+    }
+    /// Get the value already in the map.
+    pub fn existing(&self) -> &'a V {
+        self.existing
+    }
+    /// Take the rejected key and value back.
+    pub fn into_inner(self) -> (K, V) {
+        (self.key, self.value)
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for DuplicateKey<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DuplicateKey")
+            .field("key", &self.key)
+            .field("value", &self.value)
+            .field("existing", self.existing)
+            .finish()
+    }
+}
+
+impl<K: Debug, V> Display for DuplicateKey<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "duplicated key {:?}", self.key)
+    }
+}
+
+impl<K: Debug, V: Debug> Error for DuplicateKey<'_, K, V> {}
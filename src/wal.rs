@@ -0,0 +1,193 @@
+//! Persistence of [PinnedList](crate::PinnedList) and [PinnedMap](crate::PinnedMap)
+//! to append-only log files on local disk.
+//!
+//! Every push or insert is appended to the log as a record,
+//! encoded by a user-supplied [Codec].
+//! Opening the same path again replays the records to rebuild the container.
+//!
+//! Records are written to the file before the call returns,
+//! so they survive a crash of the process.
+//! Call `sync` to make them survive a crash of the system as well.
+//! A record torn by a crash at the end of the log is detected by its checksum,
+//! and cut off when the log is opened.
+//! A corrupted record before the end fails the opening instead.
+//! The log file is locked while it is open.
+//!
+//! Only available with the `wal` feature.
+
+use alloc::vec::Vec;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+pub use list::DurableList;
+pub use map::DurableMap;
+
+mod list;
+mod map;
+
+/// Converts items to and from the bytes of a record.
+///
+/// ```rust
+/// use pinned_bucket::wal::Codec;
+/// use std::io;
+///
+/// struct Utf8;
+///
+/// impl Codec<String> for Utf8 {
+///     fn encode(&self, item: &String, buf: &mut Vec<u8>) {
+///         buf.extend_from_slice(item.as_bytes());
+///     }
+///     fn decode(&self, bytes: &[u8]) -> io::Result<String> {
+///         String::from_utf8(bytes.to_vec()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+///     }
+/// }
+/// ```
+pub trait Codec<T> {
+    /// Append the bytes of `item` to `buf`.
+    fn encode(&self, item: &T, buf: &mut Vec<u8>);
+    /// Decode the bytes produced by [Codec::encode].
+    fn decode(&self, bytes: &[u8]) -> io::Result<T>;
+}
+
+/// Length and checksum before each record.
+const HEADER: usize = 8;
+
+/// FNV-1a, which is enough to detect torn writes.
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |h, b| {
+        (h ^ u32::from(*b)).wrapping_mul(0x0100_0193)
+    })
+}
+
+fn frame(payload: &[u8], buf: &mut Vec<u8>) {
+    let len = u32::try_from(payload.len()).expect("record too large");
+    buf.extend_from_slice(&len.to_le_bytes());
+    buf.extend_from_slice(&checksum(payload).to_le_bytes());
+    buf.extend_from_slice(payload);
+}
+
+fn invalid_data(error: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Flush the directory containing `path`, so that a rename in it is durable.
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+/// Directories can't be opened as files to be flushed elsewhere.
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// An append-only file of records.
+#[derive(Debug)]
+struct Log {
+    path: PathBuf,
+    file: File,
+    /// Length of the valid part of the file.
+    len: u64,
+    /// Number of records in the file.
+    records: usize,
+}
+
+impl Log {
+    /// Open or create the log at `path`, and call `f` on each record in order.
+    ///
+    /// The file is locked exclusively until the log is dropped.
+    /// A record cut off by the end of the file is torn, and dropped.
+    /// Other records failing the checksum are corrupted, and returned as errors.
+    fn open(path: &Path, mut f: impl FnMut(&[u8]) -> io::Result<()>) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        file.try_lock()?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        let mut pos = 0;
+        let mut records = 0;
+        while let Some(header) = bytes.get(pos..pos + HEADER) {
+            let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+            let sum = u32::from_le_bytes(header[4..].try_into().unwrap());
+            let end = pos + HEADER + len;
+            let Some(payload) = bytes.get(pos + HEADER..end) else {
+                break;
+            };
+            if checksum(payload) != sum {
+                if end < bytes.len() {
+                    return Err(invalid_data("corrupted record"));
+                }
+                break;
+            }
+            f(payload)?;
+            pos = end;
+            records += 1;
+        }
+        // Cut off the torn record, so that new records are readable.
+        let len = pos as u64;
+        if len < bytes.len() as u64 {
+            file.set_len(len)?;
+        }
+        Ok(Self {
+            path: path.to_owned(),
+            file,
+            len,
+            records,
+        })
+    }
+    fn append(&mut self, payload: &[u8]) -> io::Result<()> {
+        let mut buf = Vec::with_capacity(HEADER + payload.len());
+        frame(payload, &mut buf);
+        if let Err(e) = self.file.write_all(&buf) {
+            // Don't leave a partial record before the next one.
+            let _ = self.file.set_len(self.len);
+            return Err(e);
+        }
+        self.len += buf.len() as u64;
+        self.records += 1;
+        Ok(())
+    }
+    fn sync(&self) -> io::Result<()> {
+        self.file.sync_data()
+    }
+    /// Replace the whole log with `payloads`.
+    ///
+    /// The new log is written aside and renamed over the old one,
+    /// so a crash leaves either of them intact.
+    /// The directory is flushed after that, so that the rename is not lost.
+    fn rewrite(&mut self, payloads: impl IntoIterator<Item = Vec<u8>>) -> io::Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".compact");
+        let tmp = PathBuf::from(tmp);
+        let mut buf = Vec::new();
+        let mut records = 0;
+        for payload in payloads {
+            frame(&payload, &mut buf);
+            records += 1;
+        }
+        let mut file = File::create(&tmp)?;
+        // Keep the log locked after the rename.
+        file.try_lock()?;
+        file.write_all(&buf)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)?;
+        // The position is at the end, and nobody else writes to it.
+        // Swapped in before flushing the directory,
+        // so that records are never appended to the replaced file.
+        self.file = file;
+        self.len = buf.len() as u64;
+        self.records = records;
+        sync_dir(&self.path)
+    }
+}
@@ -0,0 +1,99 @@
+use super::{Codec, Log};
+use crate::{PinnedList, PANIC};
+use alloc::vec::Vec;
+use core::ops::Deref;
+use std::{io, path::Path, sync::Mutex};
+
+/// A [PinnedList] recording every push to a log file.
+///
+/// It dereferences to the [PinnedList] for reading.
+/// Items pushed through that are not recorded.
+///
+/// ```rust
+/// use pinned_bucket::wal::*;
+/// use std::io;
+///
+/// struct Le;
+///
+/// impl Codec<u64> for Le {
+///     fn encode(&self, item: &u64, buf: &mut Vec<u8>) {
+///         buf.extend_from_slice(&item.to_le_bytes());
+///     }
+///     fn decode(&self, bytes: &[u8]) -> io::Result<u64> {
+///         let bytes = bytes.try_into().map_err(|_| io::ErrorKind::InvalidData)?;
+///         Ok(u64::from_le_bytes(bytes))
+///     }
+/// }
+///
+/// # fn main() -> io::Result<()> {
+/// let path = std::env::temp_dir().join(format!("pinned-bucket-doc-{}.log", std::process::id()));
+/// # let _ = std::fs::remove_file(&path);
+/// let v = DurableList::open(&path, Le)?;
+/// let a = v.push(1)?;
+/// v.push(2)?;
+/// assert_eq!(a, &1);
+/// drop(v);
+///
+/// let v = DurableList::open(&path, Le)?;
+/// assert_eq!(v.len(), 2);
+/// assert_eq!(v[1], 2);
+/// # drop(v);
+/// # std::fs::remove_file(&path)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct DurableList<T, C> {
+    list: PinnedList<T>,
+    codec: C,
+    log: Mutex<Log>,
+}
+
+impl<T, C: Codec<T>> DurableList<T, C> {
+    /// Open the log at `path`, or create an empty one,
+    /// and push the recorded items in order.
+    ///
+    /// A torn record at the end of the log is dropped,
+    /// while errors of [Codec::decode] are returned,
+    /// and so is [io::ErrorKind::InvalidData] for a corrupted record before the end.
+    /// Fails with [io::ErrorKind::WouldBlock] if the log is already open.
+    pub fn open(path: impl AsRef<Path>, codec: C) -> io::Result<Self> {
+        let list = PinnedList::new();
+        let log = Log::open(path.as_ref(), |bytes| {
+            list.push(codec.decode(bytes)?);
+            Ok(())
+        })?;
+        Ok(Self {
+            list,
+            codec,
+            log: Mutex::new(log),
+        })
+    }
+    /// Record an item to the log, then push it into the [PinnedList]
+    /// and return the reference to it.
+    ///
+    /// Nothing is pushed if the record fails to be written.
+    pub fn push(&self, t: T) -> io::Result<&T> {
+        let mut buf = Vec::new();
+        self.codec.encode(&t, &mut buf);
+        // Records and items are kept in the same order.
+        let mut log = self.log.lock().expect(PANIC);
+        log.append(&buf)?;
+        Ok(self.list.push(t))
+    }
+    /// Flush the log to the disk.
+    pub fn sync(&self) -> io::Result<()> {
+        self.log.lock().expect(PANIC).sync()
+    }
+    /// Stop recording and take the [PinnedList] out.
+    pub fn into_inner(self) -> PinnedList<T> {
+        self.list
+    }
+}
+
+impl<T, C> Deref for DurableList<T, C> {
+    type Target = PinnedList<T>;
+    fn deref(&self) -> &PinnedList<T> {
+        &self.list
+    }
+}
@@ -0,0 +1,208 @@
+use super::{invalid_data, Codec, Log};
+use crate::{PinnedMap, Policy, Shadow};
+use alloc::vec::Vec;
+use core::ops::Deref;
+use std::{
+    io,
+    path::Path,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// A [PinnedMap] recording every insert to a log file.
+///
+/// Keys and values are encoded by the same [Codec],
+/// which implements both `Codec<K>` and `Codec<V>`.
+///
+/// Shadowed items are kept in the log until it is compacted,
+/// either by [DurableMap::compact],
+/// or by [DurableMap::insert] once [DurableMap::records]
+/// reaches 64 and grows beyond 4 times [PinnedMap::len].
+/// Only the latest item under each key is restored after that.
+///
+/// It dereferences to the [PinnedMap] for reading.
+/// Items inserted through that are not recorded.
+///
+/// ```rust
+/// use pinned_bucket::wal::*;
+/// use std::io;
+///
+/// struct Utf8;
+///
+/// impl Codec<String> for Utf8 {
+///     fn encode(&self, item: &String, buf: &mut Vec<u8>) {
+///         buf.extend_from_slice(item.as_bytes());
+///     }
+///     fn decode(&self, bytes: &[u8]) -> io::Result<String> {
+///         String::from_utf8(bytes.to_vec()).map_err(|_| io::ErrorKind::InvalidData.into())
+///     }
+/// }
+///
+/// # fn main() -> io::Result<()> {
+/// let path = std::env::temp_dir().join(format!("pinned-bucket-doc-map-{}.log", std::process::id()));
+/// # let _ = std::fs::remove_file(&path);
+/// let v = DurableMap::open(&path, Utf8)?;
+/// v.insert("a".to_owned(), "1".to_owned())?;
+/// v.insert("a".to_owned(), "2".to_owned())?;
+/// assert_eq!(v.records(), 2);
+/// drop(v);
+///
+/// let v = DurableMap::open(&path, Utf8)?;
+/// assert_eq!(v.get("a").map(String::as_str), Some("2"));
+/// assert_eq!(v.shadowed_len(), 1);
+/// v.compact()?;
+/// assert_eq!(v.records(), 1);
+/// # drop(v);
+/// # std::fs::remove_file(&path)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct DurableMap<K, V, C, P = Shadow> {
+    map: PinnedMap<K, V, P>,
+    codec: C,
+    log: Mutex<Log>,
+}
+
+/// Records per item allowed before [DurableMap::insert] compacts the log.
+const COMPACT_RATIO: usize = 4;
+
+/// Records below which the log is never compacted automatically.
+const COMPACT_MIN: usize = 64;
+
+impl<K: Ord, V, C: Codec<K> + Codec<V>> DurableMap<K, V, C> {
+    /// Open the log at `path` with the default [Shadow] policy,
+    /// or create an empty one.
+    ///
+    /// See [DurableMap::open_with_policy].
+    pub fn open(path: impl AsRef<Path>, codec: C) -> io::Result<Self> {
+        Self::open_with_policy(path, codec, Shadow)
+    }
+}
+
+impl<K: Ord, V, C: Codec<K> + Codec<V>, P> DurableMap<K, V, C, P> {
+    /// Open the log at `path` with given [Policy], or create an empty one,
+    /// and insert the recorded items in order.
+    ///
+    /// Recorded items always shadow the previous ones under the same key,
+    /// as only items actually inserted are recorded.
+    /// A torn record at the end of the log is dropped,
+    /// while errors of [Codec::decode] are returned,
+    /// and so is [io::ErrorKind::InvalidData] for a corrupted record before the end.
+    /// Fails with [io::ErrorKind::WouldBlock] if the log is already open.
+    pub fn open_with_policy(path: impl AsRef<Path>, codec: C, policy: P) -> io::Result<Self>
+    where
+        P: Copy,
+    {
+        let map = PinnedMap::with_policy(policy);
+        let log = Log::open(path.as_ref(), |bytes| {
+            let (key, value) = decode(&codec, bytes)?;
+            map.replace(key, value);
+            Ok(())
+        })?;
+        Ok(Self {
+            map,
+            codec,
+            log: Mutex::new(log),
+        })
+    }
+    /// Record an item to the log, then insert it into the [PinnedMap]
+    /// as [PinnedMap::insert] does.
+    ///
+    /// Only items actually inserted are recorded.
+    /// Nothing is inserted if the record fails to be written,
+    /// or if the log fails to be compacted before that.
+    pub fn insert(&self, key: K, value: V) -> io::Result<P::Output<'_, K, V>>
+    where
+        P: Policy,
+    {
+        let mut buf = Vec::new();
+        encode(&self.codec, &key, &value, &mut buf);
+        // Records and items are kept in the same order.
+        // The log is left intact if the policy panics.
+        let mut log = self.log();
+        if log.records >= COMPACT_MIN && log.records > COMPACT_RATIO * self.map.len() {
+            self.rewrite(&mut log)?;
+        }
+        // Absent keys are checked and recorded under the lock of the map,
+        // so that nobody inserts them in between.
+        let inserted = self.map.try_insert_if(key, value, || log.append(&buf))?;
+        let mut written = Ok(());
+        let output = match inserted {
+            Ok(v) => P::inserted(v),
+            Err(duplicate) => {
+                let existing = duplicate.existing();
+                P::duplicated(duplicate, |k, v| {
+                    written = log.append(&buf);
+                    match written {
+                        Ok(()) => self.map.replace(k, v).0,
+                        Err(_) => existing,
+                    }
+                })
+            }
+        };
+        written.map(|()| output)
+    }
+    /// Get the number of records in the log,
+    /// including those of shadowed items.
+    pub fn records(&self) -> usize {
+        self.log().records
+    }
+    /// Rewrite the log with the latest item under each key only.
+    ///
+    /// Shadowed items stay in the [PinnedMap],
+    /// but are not restored after reopening.
+    /// Inserts wait until it finishes.
+    pub fn compact(&self) -> io::Result<()> {
+        self.rewrite(&mut self.log())
+    }
+    /// Flush the log to the disk.
+    pub fn sync(&self) -> io::Result<()> {
+        self.log().sync()
+    }
+    /// Stop recording and take the [PinnedMap] out.
+    pub fn into_inner(self) -> PinnedMap<K, V, P> {
+        self.map
+    }
+    fn rewrite(&self, log: &mut Log) -> io::Result<()> {
+        log.rewrite(self.map.iter().map(|(k, v)| {
+            let mut buf = Vec::new();
+            encode(&self.codec, k, v, &mut buf);
+            buf
+        }))
+    }
+    /// Lock the log.
+    ///
+    /// A panic while it is locked, for example by the [Policy],
+    /// happens before anything is written, so the log is still intact.
+    fn log(&self) -> MutexGuard<'_, Log> {
+        self.log.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Encode a key with its length, followed by the value.
+fn encode<K, V, C: Codec<K> + Codec<V>>(codec: &C, key: &K, value: &V, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&[0; 4]);
+    codec.encode(key, buf);
+    let len = u32::try_from(buf.len() - 4).expect("key too large");
+    buf[..4].copy_from_slice(&len.to_le_bytes());
+    codec.encode(value, buf);
+}
+
+fn decode<K, V, C: Codec<K> + Codec<V>>(codec: &C, bytes: &[u8]) -> io::Result<(K, V)> {
+    let (len, bytes) = bytes
+        .split_first_chunk::<4>()
+        .ok_or_else(|| invalid_data("missing key length"))?;
+    let len = u32::from_le_bytes(*len) as usize;
+    if len > bytes.len() {
+        return Err(invalid_data("key length out of range"));
+    }
+    let (key, value) = bytes.split_at(len);
+    Ok((codec.decode(key)?, codec.decode(value)?))
+}
+
+impl<K, V, C, P> Deref for DurableMap<K, V, C, P> {
+    type Target = PinnedMap<K, V, P>;
+    fn deref(&self) -> &PinnedMap<K, V, P> {
+        &self.map
+    }
+}
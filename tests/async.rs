@@ -0,0 +1,197 @@
+#![cfg(feature = "async")]
+
+use pinned_bucket::{PinnedList, PinnedMap};
+use std::{
+    future::Future,
+    pin::pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+    time::Duration,
+};
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// A minimal executor, which parks the current thread until woken up.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        thread::park();
+    }
+}
+
+/// Poll two futures on the current thread until both are ready.
+fn join<A: Future, B: Future>(a: A, b: B) -> (A::Output, B::Output) {
+    let mut a = pin!(a);
+    let mut b = pin!(b);
+    let (mut ra, mut rb) = (None, None);
+    block_on(std::future::poll_fn(|cx| {
+        if ra.is_none() {
+            if let Poll::Ready(v) = a.as_mut().poll(cx) {
+                ra = Some(v);
+            }
+        }
+        if rb.is_none() {
+            if let Poll::Ready(v) = b.as_mut().poll(cx) {
+                rb = Some(v);
+            }
+        }
+        if ra.is_some() && rb.is_some() {
+            Poll::Ready((ra.take().unwrap(), rb.take().unwrap()))
+        } else {
+            Poll::Pending
+        }
+    }))
+}
+
+/// Yield to the executor once.
+async fn yield_now() {
+    let mut yielded = false;
+    std::future::poll_fn(|cx| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+#[test]
+fn get_or_insert_with_async() {
+    let map = PinnedMap::new();
+    let a = block_on(map.get_or_insert_with_async(1, || async { 2 }));
+    let b = block_on(map.get_or_insert_with_async(1, || async { unreachable!() }));
+    assert_eq!(a, &2);
+    assert_eq!(a as *const i32, b as *const i32);
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn deduplicate_on_one_thread() {
+    let map = PinnedMap::new();
+    let calls = AtomicUsize::new(0);
+    let init = || async {
+        calls.fetch_add(1, Ordering::SeqCst);
+        for _ in 0..4 {
+            yield_now().await;
+        }
+        3
+    };
+    let (a, b) = join(
+        map.get_or_insert_with_async(1, init),
+        map.get_or_insert_with_async(1, init),
+    );
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(a as *const i32, b as *const i32);
+    assert_eq!(a, &3);
+}
+
+#[test]
+fn deduplicate_across_threads() {
+    let map = PinnedMap::new();
+    let calls = AtomicUsize::new(0);
+    thread::scope(|s| {
+        for _ in 0..8 {
+            s.spawn(|| {
+                block_on(map.get_or_insert_with_async(0, || async {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    for _ in 0..4 {
+                        thread::sleep(Duration::from_millis(1));
+                        yield_now().await;
+                    }
+                    42
+                }))
+            });
+        }
+    });
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(map.get(&0), Some(&42));
+}
+
+#[test]
+fn cancelled_initializer() {
+    let map = PinnedMap::new();
+    {
+        let mut first = pin!(map.get_or_insert_with_async(1, || async {
+            yield_now().await;
+            unreachable!()
+        }));
+        let waker = Waker::noop();
+        let mut cx = Context::from_waker(waker);
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+    }
+    assert!(map.is_empty());
+    let b = block_on(map.get_or_insert_with_async(1, || async { 2 }));
+    assert_eq!(b, &2);
+}
+
+#[test]
+#[should_panic = "reentrant initialization of the same key"]
+fn reentrant_same_key() {
+    let map = PinnedMap::new();
+    block_on(map.get_or_insert_with_async(1, || async {
+        yield_now().await;
+        *map.get_or_insert_with_async(1, || async { 2 }).await
+    }));
+}
+
+#[test]
+fn reentrant_other_key() {
+    let map = PinnedMap::new();
+    let a = block_on(map.get_or_insert_with_async(1, || async {
+        yield_now().await;
+        *map.get_or_insert_with_async(2, || async { 3 }).await + 1
+    }));
+    assert_eq!(a, &4);
+    assert_eq!(map.get(&2), Some(&3));
+}
+
+#[test]
+fn insert_while_pending() {
+    let map = PinnedMap::new();
+    let (a, b) = join(
+        map.get_or_insert_with_async(1, || async {
+            yield_now().await;
+            2
+        }),
+        async { map.insert(1, 3) },
+    );
+    assert_eq!(a, &3);
+    assert_eq!(b, &3);
+}
+
+#[test]
+fn cursor_across_threads() {
+    let list = PinnedList::new();
+    thread::scope(|s| {
+        let mut cursor = list.cursor();
+        let reader = s.spawn(move || {
+            let mut seen = Vec::new();
+            while seen.len() < 100 {
+                seen.extend(block_on(cursor.next_batch_async()).copied());
+            }
+            seen
+        });
+        for i in 0..100 {
+            list.push(i);
+        }
+        assert_eq!(reader.join().unwrap(), (0..100).collect::<Vec<_>>());
+    });
+}
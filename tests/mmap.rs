@@ -0,0 +1,74 @@
+#![cfg(feature = "mmap")]
+
+use pinned_bucket::mmap::MmapList;
+use std::{fs, io, thread};
+
+#[test]
+fn anonymous_chunks() {
+    let v = MmapList::<u64>::anonymous();
+    let first = v.push(0).unwrap();
+    // More than one chunk.
+    thread::scope(|s| {
+        for t in 0..4 {
+            let v = &v;
+            s.spawn(move || {
+                for i in 0..100_000 {
+                    v.push(t * 100_000 + i).unwrap();
+                }
+            });
+        }
+    });
+    assert_eq!(v.len(), 400_001);
+    assert_eq!(first as *const u64, &v[0] as *const u64);
+    let mut all: Vec<_> = (1..v.len()).map(|i| v[i]).collect();
+    all.sort_unstable();
+    assert!(all.iter().copied().eq(0..400_000));
+    assert_eq!(v.get(400_001), None);
+}
+
+#[test]
+fn reopen() {
+    let path = std::env::temp_dir().join(format!(
+        "pinned-bucket-test-reopen-{}.mmap",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    let v = MmapList::<[u16; 3]>::open(&path).unwrap();
+    for i in 0..200_000u32 {
+        v.push([i as u16, (i >> 16) as u16, 7]).unwrap();
+    }
+    v.flush().unwrap();
+    drop(v);
+
+    let v = MmapList::<[u16; 3]>::open(&path).unwrap();
+    assert_eq!(v.len(), 200_000);
+    assert_eq!(v[199_999], [(199_999u32 & 0xffff) as u16, 3, 7]);
+    v.push([1, 2, 3]).unwrap();
+    drop(v);
+
+    let e = MmapList::<u64>::open(&path).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    let v = MmapList::<[u16; 3]>::open(&path).unwrap();
+    assert_eq!(v.len(), 200_001);
+    drop(v);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn open_twice() {
+    let path = std::env::temp_dir().join(format!(
+        "pinned-bucket-test-twice-{}.mmap",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    let v = MmapList::<u64>::open(&path).unwrap();
+    v.push(1).unwrap();
+    let e = MmapList::<u64>::open(&path).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+    drop(v);
+
+    let v = MmapList::<u64>::open(&path).unwrap();
+    assert_eq!(v.len(), 1);
+    drop(v);
+    fs::remove_file(&path).unwrap();
+}
@@ -14,3 +14,33 @@ fn insert() {
         assert_eq!(*k * *k, *v);
     }
 }
+
+#[cfg(feature = "rayon")]
+#[test]
+fn collect() {
+    use pinned_bucket::{KeepFirst, PinnedList, ReturnError};
+
+    let list: PinnedList<_> = (0..1000).into_par_iter().collect();
+    assert_eq!(list.len(), 1000);
+    assert_eq!(list.par_iter().sum::<i32>(), 999 * 1000 / 2);
+    let a = &list[0];
+    (&list).par_extend((0..1000).into_par_iter());
+    assert_eq!(list.len(), 2000);
+    assert_eq!(a as *const i32, &list[0] as *const i32);
+    assert_eq!((&list).into_par_iter().count(), 2000);
+
+    let map: PinnedMap<_, _, KeepFirst> = (0..1000).into_par_iter().map(|i| (i % 10, i)).collect();
+    assert_eq!(map.len(), 10);
+    // Items are inserted in order, so the first ones are kept.
+    assert!(map.par_iter().all(|(k, v)| k == v));
+    // Rejected items are dropped.
+    let map: PinnedMap<_, _, ReturnError> =
+        (0..1000).into_par_iter().map(|i| (i % 10, i)).collect();
+    assert_eq!(map.len(), 10);
+    assert_eq!(map.shadowed_len(), 0);
+    assert!(map.par_iter().all(|(k, v)| k == v));
+    let mut map = PinnedMap::new();
+    map.par_extend((0..1000).into_par_iter().map(|i| (i % 10, i)));
+    assert_eq!(map.shadowed_len(), 990);
+    assert!(map.par_iter().all(|(k, v)| *v == 990 + k));
+}
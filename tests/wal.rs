@@ -0,0 +1,202 @@
+#![cfg(feature = "wal")]
+
+use pinned_bucket::{
+    wal::{Codec, DurableList, DurableMap},
+    KeepFirst, Panic,
+};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::PathBuf,
+    sync::Barrier,
+};
+
+#[derive(Debug)]
+struct Le;
+
+impl Codec<u32> for Le {
+    fn encode(&self, item: &u32, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&item.to_le_bytes());
+    }
+    fn decode(&self, bytes: &[u8]) -> io::Result<u32> {
+        let bytes = bytes.try_into().map_err(|_| io::ErrorKind::InvalidData)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+}
+
+/// A log file removed on drop.
+struct TempLog(PathBuf);
+
+impl TempLog {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "pinned-bucket-test-{}-{}.log",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        Self(path)
+    }
+}
+
+impl Drop for TempLog {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn torn_record() {
+    let log = TempLog::new("torn");
+    let v = DurableList::open(&log.0, Le).unwrap();
+    for i in 0..3 {
+        v.push(i).unwrap();
+    }
+    drop(v);
+    // A crash in the middle of writing a record.
+    let mut file = OpenOptions::new().append(true).open(&log.0).unwrap();
+    file.write_all(&[8, 0, 0, 0, 1]).unwrap();
+    drop(file);
+
+    let v = DurableList::open(&log.0, Le).unwrap();
+    assert_eq!(
+        v.cursor().next_batch().copied().collect::<Vec<_>>(),
+        [0, 1, 2]
+    );
+    v.push(3).unwrap();
+    drop(v);
+    let v = DurableList::open(&log.0, Le).unwrap();
+    assert_eq!(
+        v.cursor().next_batch().copied().collect::<Vec<_>>(),
+        [0, 1, 2, 3]
+    );
+}
+
+#[test]
+fn map_policy() {
+    let log = TempLog::new("policy");
+    let v = DurableMap::open_with_policy(&log.0, Le, KeepFirst).unwrap();
+    assert_eq!(v.insert(1, 10).unwrap(), &10);
+    assert_eq!(v.insert(1, 11).unwrap(), &10);
+    v.insert(2, 20).unwrap();
+    assert_eq!(v.records(), 2);
+    drop(v);
+
+    let v = DurableMap::open(&log.0, Le).unwrap();
+    assert_eq!(v.get(&1), Some(&10));
+    v.insert(1, 12).unwrap();
+    v.insert(1, 13).unwrap();
+    v.compact().unwrap();
+    assert_eq!(v.records(), 2);
+    v.insert(3, 30).unwrap();
+    v.sync().unwrap();
+    drop(v);
+
+    let v = DurableMap::open(&log.0, Le).unwrap();
+    assert_eq!(
+        v.iter().collect::<Vec<_>>(),
+        [(&1, &13), (&2, &20), (&3, &30)]
+    );
+    assert_eq!(v.shadowed_len(), 0);
+}
+
+#[test]
+fn map_policy_panic() {
+    let log = TempLog::new("panic");
+    let v = DurableMap::open_with_policy(&log.0, Le, Panic).unwrap();
+    v.insert(1, 10).unwrap();
+    assert!(catch_unwind(AssertUnwindSafe(|| v.insert(1, 11))).is_err());
+    assert_eq!(v.records(), 1);
+    v.sync().unwrap();
+    v.insert(2, 20).unwrap();
+    drop(v);
+
+    let v = DurableMap::open(&log.0, Le).unwrap();
+    assert_eq!(v.iter().collect::<Vec<_>>(), [(&1, &10), (&2, &20)]);
+}
+
+#[test]
+fn map_compact_automatically() {
+    let log = TempLog::new("auto");
+    let v = DurableMap::open(&log.0, Le).unwrap();
+    for i in 0..1000 {
+        v.insert(i % 4, i).unwrap();
+        assert!(v.records() <= 64);
+    }
+    drop(v);
+
+    let v = DurableMap::open(&log.0, Le).unwrap();
+    assert_eq!(
+        v.iter().collect::<Vec<_>>(),
+        [(&0, &996), (&1, &997), (&2, &998), (&3, &999)]
+    );
+}
+
+#[test]
+fn corrupted_record() {
+    let log = TempLog::new("corrupted");
+    let v = DurableList::open(&log.0, Le).unwrap();
+    for i in 0..3 {
+        v.push(i).unwrap();
+    }
+    drop(v);
+    // Flip a byte in the payload of the second record.
+    let mut bytes = fs::read(&log.0).unwrap();
+    bytes[12 + 8] ^= 1;
+    fs::write(&log.0, &bytes).unwrap();
+
+    let e = DurableList::open(&log.0, Le).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    // Nothing is cut off.
+    assert_eq!(fs::read(&log.0).unwrap(), bytes);
+}
+
+#[test]
+fn open_twice() {
+    let log = TempLog::new("twice");
+    let v = DurableMap::open(&log.0, Le).unwrap();
+    v.insert(1, 10).unwrap();
+    let e = DurableMap::<u32, u32, _>::open(&log.0, Le).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+    // Still locked after compaction.
+    v.compact().unwrap();
+    let e = DurableMap::<u32, u32, _>::open(&log.0, Le).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+    drop(v);
+
+    let v = DurableMap::open(&log.0, Le).unwrap();
+    assert_eq!(v.get(&1), Some(&10));
+}
+
+#[test]
+fn map_insert_race() {
+    let log = TempLog::new("race");
+    let v = DurableMap::open_with_policy(&log.0, Le, KeepFirst).unwrap();
+    let barrier = Barrier::new(2);
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            barrier.wait();
+            for i in 0..10_000 {
+                v.insert(i, 1).unwrap();
+            }
+        });
+        s.spawn(|| {
+            barrier.wait();
+            for i in 1..10_000 {
+                // Right behind the recorded inserts, but not recorded.
+                while !v.contains_key(&(i - 1)) {
+                    std::thread::yield_now();
+                }
+                let _ = v.insert(i, 2);
+            }
+        });
+    });
+    let memory = v.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+    drop(v);
+
+    let v = DurableMap::open(&log.0, Le).unwrap();
+    for (k, value) in v.iter() {
+        assert!(memory.contains(&(*k, *value)));
+    }
+}